@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+
+use crate::error::Error;
+use crate::object::Movement;
+use crate::surface::Surface;
+
+/// How quickly player-controlled entities get up to speed and slow down again, as read from
+/// `assets/movement.json`.
+///
+/// Rates are the change in horizontal speed per tick. Rates on the ground are scaled by the
+/// friction of the surface, so that slippery surfaces are as hard to get going on as they are
+/// to stop on.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+#[serde(default)]
+pub struct Acceleration {
+    pub walk_speed: f32,
+    pub run_speed: f32,
+    pub ground_acceleration: f32,
+    pub ground_deceleration: f32,
+    pub air_acceleration: f32,
+    pub air_deceleration: f32,
+    /// The rate used on the ground when moving against the current direction of travel.
+    pub turn_around: f32,
+}
+
+impl Default for Acceleration {
+    fn default() -> Self {
+        Self {
+            walk_speed: 6.0,
+            run_speed: 10.0,
+            ground_acceleration: 0.8,
+            ground_deceleration: 1.0,
+            air_acceleration: 0.4,
+            air_deceleration: 0.1,
+            turn_around: 1.6,
+        }
+    }
+}
+
+impl Acceleration {
+    pub fn load() -> Result<Self, Error> {
+        let file = File::open("assets/movement.json")?;
+        let reader = io::BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Steers the movement's horizontal speed towards the direction held, which is -1.0 for
+    /// left, 1.0 for right, or 0.0 to come to a stop.
+    pub fn drive(&self, movement: &mut Movement, direction: f32, running: bool) {
+        let speed = movement.get_speed().x;
+        let target = direction
+            * if running {
+                self.run_speed
+            } else {
+                self.walk_speed
+            };
+
+        let rate = match movement.get_surface() {
+            Some(surface) => {
+                let grip = surface.friction / Surface::default().friction;
+                let rate = if speed * direction < 0.0 {
+                    self.turn_around
+                } else if target.abs() > speed.abs() {
+                    self.ground_acceleration
+                } else {
+                    self.ground_deceleration
+                };
+                rate * grip
+            }
+            None => {
+                if direction != 0.0 && target.abs() > speed.abs() || speed * direction < 0.0 {
+                    self.air_acceleration
+                } else {
+                    self.air_deceleration
+                }
+            }
+        };
+
+        movement.drive(target, rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grounded_on(surface: Surface) -> Movement {
+        let mut movement = Movement::new();
+        movement.set_grounded(true);
+        movement.set_surface(Some(surface));
+        movement
+    }
+
+    fn drive(acceleration: &Acceleration, movement: &mut Movement, direction: f32, ticks: u32) {
+        for _ in 0..ticks {
+            acceleration.drive(movement, direction, false);
+        }
+    }
+
+    #[test]
+    fn speeds_up_gradually_to_walking_speed() {
+        let acceleration = Acceleration::default();
+        let mut movement = grounded_on(Surface::default());
+
+        drive(&acceleration, &mut movement, 1.0, 1);
+        assert_eq!(movement.get_speed().x, acceleration.ground_acceleration);
+
+        drive(&acceleration, &mut movement, 1.0, 100);
+        assert_eq!(movement.get_speed().x, acceleration.walk_speed);
+    }
+
+    #[test]
+    fn running_reaches_running_speed() {
+        let acceleration = Acceleration::default();
+        let mut movement = grounded_on(Surface::default());
+
+        for _ in 0..100 {
+            acceleration.drive(&mut movement, -1.0, true);
+        }
+        assert_eq!(movement.get_speed().x, -acceleration.run_speed);
+    }
+
+    #[test]
+    fn turning_around_uses_its_own_rate() {
+        let acceleration = Acceleration::default();
+        let mut movement = grounded_on(Surface::default());
+        drive(&acceleration, &mut movement, 1.0, 100);
+
+        drive(&acceleration, &mut movement, -1.0, 1);
+        assert_eq!(
+            movement.get_speed().x,
+            acceleration.walk_speed - acceleration.turn_around
+        );
+
+        drive(&acceleration, &mut movement, 0.0, 100);
+        assert_eq!(movement.get_speed().x, 0.0);
+    }
+
+    #[test]
+    fn slippery_surfaces_scale_the_ground_rates() {
+        let acceleration = Acceleration::default();
+        let ice = Surface {
+            friction: Surface::default().friction / 4.0,
+            ..Surface::default()
+        };
+        let mut movement = grounded_on(ice);
+
+        drive(&acceleration, &mut movement, 1.0, 1);
+        assert_eq!(
+            movement.get_speed().x,
+            acceleration.ground_acceleration / 4.0
+        );
+    }
+
+    #[test]
+    fn air_rates_apply_off_the_ground() {
+        let acceleration = Acceleration::default();
+        let mut movement = Movement::new();
+
+        drive(&acceleration, &mut movement, 1.0, 1);
+        assert_eq!(movement.get_speed().x, acceleration.air_acceleration);
+
+        drive(&acceleration, &mut movement, 0.0, 1);
+        assert_eq!(
+            movement.get_speed().x,
+            acceleration.air_acceleration - acceleration.air_deceleration
+        );
+    }
+}
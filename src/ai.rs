@@ -0,0 +1,362 @@
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::input::JUMP_FORCE;
+use crate::map::Map;
+use crate::navigation::{Move, NavGraph};
+use crate::object::Collision;
+use crate::rect::Rect;
+
+/// What an AI controller can see of the world when deciding what to do next.
+pub struct Surroundings<'m> {
+    /// Where the controlled entity is.
+    pub rect: Rect<f32>,
+    pub grounded: bool,
+    /// Where the nearest entity with the controller's target tag is, if it wants one.
+    pub target: Option<Rect<f32>>,
+    pub map: &'m Map<'m>,
+    /// The navigation graph for the controlled entity's reach, if it finds its way by one.
+    pub graph: Option<&'m NavGraph>,
+}
+
+impl<'m> Surroundings<'m> {
+    /// Whether there's a wall directly ahead, too tall to step up onto.
+    pub fn wall_ahead(&self, direction: f32) -> bool {
+        let step_height = self.rect.width / 2.0;
+        let probe = Rect::new(
+            self.front(direction) + direction,
+            self.rect.y + 1.0,
+            1.0,
+            self.rect.height - step_height - 1.0,
+        );
+        self.map.collidable_tiles(&probe).iter().any(|cell| {
+            cell.object.get_collision() == Collision::Solid
+                && !cell.object.get_shape().is_slope()
+                && cell.object.collision_rect().has_overlap(&probe)
+        })
+    }
+
+    /// Whether there's no floor just ahead, so that walking on would mean falling.
+    pub fn ledge_ahead(&self, direction: f32) -> bool {
+        let probe = Rect::new(
+            self.front(direction) + direction,
+            self.rect.y + self.rect.height + 1.0,
+            1.0,
+            1.0,
+        );
+        !self.map.collidable_tiles(&probe).iter().any(|cell| {
+            cell.object.get_collision() != Collision::None && cell.object.rect.has_overlap(&probe)
+        })
+    }
+
+    /// The first move along the path to the target, and where its feet should end up after
+    /// making it. `None` if there's no graph, no path, or the target is on the same spot.
+    pub fn next_step(&self, target: &Rect<f32>) -> Option<(Move, Point2<f32>)> {
+        let graph = self.graph?;
+        let path = graph.find_path_between(feet(&self.rect), feet(target))?;
+        path.first().map(|step| (step.kind, graph.feet(step.tile)))
+    }
+
+    /// The x position of the edge of the rect facing in the direction.
+    fn front(&self, direction: f32) -> f32 {
+        if direction > 0.0 {
+            self.rect.x + self.rect.width
+        } else {
+            self.rect.x
+        }
+    }
+}
+
+/// Walks back and forth, turning around at walls and at the edges of whatever it's walking on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Patrol {
+    direction: f32,
+}
+
+impl Patrol {
+    pub fn new() -> Self {
+        Self { direction: 1.0 }
+    }
+
+    pub fn think(&mut self, surroundings: &Surroundings) {
+        if !surroundings.grounded {
+            return;
+        }
+
+        if surroundings.wall_ahead(self.direction) || surroundings.ledge_ahead(self.direction) {
+            self.direction = -self.direction;
+        }
+    }
+
+    pub fn get_direction(&self) -> f32 {
+        self.direction
+    }
+}
+
+/// Heads for its target once it comes within `radius`, following the path there if it has a
+/// navigation graph, or else walking straight at it and jumping any walls in the way.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Chase {
+    radius: f32,
+    target: String,
+    direction: f32,
+    jump: bool,
+}
+
+impl Chase {
+    /// Chases the nearest entity with the `target` tag.
+    pub fn new<S>(radius: f32, target: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            radius,
+            target: target.into(),
+            direction: 0.0,
+            jump: false,
+        }
+    }
+
+    pub fn get_target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn think(&mut self, surroundings: &Surroundings) {
+        self.jump = false;
+        // Whatever it was heading for when it left the ground, it keeps heading for.
+        if !surroundings.grounded {
+            return;
+        }
+
+        let rect = &surroundings.rect;
+        let centre = rect.x + rect.width / 2.0;
+        let target = match &surroundings.target {
+            Some(target) if distance(rect, target) <= self.radius => target,
+            _ => {
+                self.direction = 0.0;
+                return;
+            }
+        };
+
+        if let Some((kind, feet)) = surroundings.next_step(target) {
+            self.direction = (feet.x - centre).signum();
+            self.jump = kind == Move::Jump;
+            return;
+        }
+
+        let dx = target.x + target.width / 2.0 - centre;
+        // Close enough to count as caught, so it doesn't jitter back and forth.
+        self.direction = if dx.abs() < rect.width / 2.0 {
+            0.0
+        } else {
+            dx.signum()
+        };
+        self.jump = self.direction != 0.0 && surroundings.wall_ahead(self.direction);
+    }
+
+    pub fn get_direction(&self) -> f32 {
+        self.direction
+    }
+
+    pub fn get_force(&self) -> Vector2<f32> {
+        if self.jump {
+            Vector2::new(0.0, JUMP_FORCE)
+        } else {
+            Vector2::new(0.0, 0.0)
+        }
+    }
+}
+
+/// Stays where it is, jumping every `interval` ticks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Jumper {
+    interval: u32,
+    timer: u32,
+    jump: bool,
+}
+
+impl Jumper {
+    pub fn new(interval: u32) -> Self {
+        Self {
+            interval,
+            timer: 0,
+            jump: false,
+        }
+    }
+
+    pub fn think(&mut self, surroundings: &Surroundings) {
+        self.jump = false;
+        if !surroundings.grounded {
+            return;
+        }
+
+        self.timer += 1;
+        if self.timer >= self.interval {
+            self.timer = 0;
+            self.jump = true;
+        }
+    }
+
+    pub fn get_force(&self) -> Vector2<f32> {
+        if self.jump {
+            Vector2::new(0.0, JUMP_FORCE)
+        } else {
+            Vector2::new(0.0, 0.0)
+        }
+    }
+}
+
+/// The point just above the middle of the bottom of the rect, which is in the tile it's
+/// standing on.
+fn feet(rect: &Rect<f32>) -> Point2<f32> {
+    Point2::new(rect.x + rect.width / 2.0, rect.y + rect.height - 1.0)
+}
+
+/// The distance between the centres of two rects.
+fn distance(a: &Rect<f32>, b: &Rect<f32>) -> f32 {
+    let dx = (a.x + a.width / 2.0) - (b.x + b.width / 2.0);
+    let dy = (a.y + a.height / 2.0) - (b.y + b.height / 2.0);
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::Entity;
+    use crate::testing::{self, run};
+    use crate::world::World;
+    use serde_json::json;
+
+    fn world() -> World<'static> {
+        testing::world(Map::default())
+    }
+
+    fn rect(world: &World, entity: Entity) -> Rect<f32> {
+        world.entity_manager.get_object(entity).unwrap().rect
+    }
+
+    #[test]
+    fn ledges_are_seen_where_the_floor_ends() {
+        let map = Map::default();
+        let on_floor = Surroundings {
+            rect: Rect::new(700.0, 920.0, 60.0, 60.0),
+            grounded: true,
+            target: None,
+            map: &map,
+            graph: None,
+        };
+        assert!(!on_floor.ledge_ahead(1.0));
+        assert!(!on_floor.wall_ahead(1.0));
+
+        let in_the_air = Surroundings {
+            rect: Rect::new(700.0, 500.0, 60.0, 60.0),
+            ..on_floor
+        };
+        assert!(in_the_air.ledge_ahead(-1.0));
+    }
+
+    #[test]
+    fn patrols_turn_around_at_walls() {
+        let mut world = world();
+        let walker = world.spawn("walker", 1800.0, 920.0).unwrap();
+
+        let mut furthest = 0.0;
+        for _ in 0..60 {
+            run(&mut world, 5);
+            furthest = f32::max(furthest, rect(&world, walker).x);
+        }
+
+        let wall = 29.0 * 70.0;
+        assert!(furthest > wall - 60.0 - 10.0 && furthest <= wall - 60.0);
+        assert!(rect(&world, walker).x < furthest);
+    }
+
+    #[test]
+    fn patrols_turn_around_at_ledges() {
+        let mut world = testing::world(Map::from_ascii(
+            "
+            ##########
+            #........#
+            #........#
+            #........#
+            #..####..#
+            #........#
+            ##########
+            ",
+        ));
+        let walker = world.spawn("walker", 280.0, 220.0).unwrap();
+        run(&mut world, 10);
+
+        let (mut leftmost, mut rightmost) = (f32::MAX, f32::MIN);
+        for _ in 0..120 {
+            run(&mut world, 5);
+            let rect = rect(&world, walker);
+            assert_eq!(rect.y + rect.height, 280.0);
+            leftmost = f32::min(leftmost, rect.x);
+            rightmost = f32::max(rightmost, rect.x + rect.width);
+        }
+
+        assert!(leftmost > 210.0 - 30.0 && leftmost < 210.0 + 10.0);
+        assert!(rightmost < 490.0 + 30.0 && rightmost > 490.0 - 10.0);
+    }
+
+    #[test]
+    fn chasers_only_chase_within_their_radius() {
+        let mut world = world();
+        let near = world.spawn("chaser", 400.0, 920.0).unwrap();
+        let far = world.spawn("chaser", 1800.0, 920.0).unwrap();
+        run(&mut world, 60);
+
+        assert!(rect(&world, near).x < 400.0);
+        assert_eq!(rect(&world, far).x, 1800.0);
+    }
+
+    #[test]
+    fn chasers_find_their_way_round_to_targets_out_of_reach() {
+        // The player is on a shelf too high to jump onto from below, which can only be reached
+        // by the step on the far side.
+        let mut world = testing::world(Map::from_ascii(
+            "
+            ############
+            #..........#
+            #..........#
+            #..........#
+            #@.........#
+            #########..#
+            #.........##
+            ############
+            ",
+        ));
+        let chaser = world
+            .prefabs
+            .with_overrides(
+                "chaser",
+                &json!({ "input": { "Chase": { "radius": 1000.0 } } }),
+            )
+            .unwrap()
+            .builder(140.0, 430.0);
+        let chaser = world.entity_manager.add(chaser);
+        run(&mut world, 300);
+
+        let rect = rect(&world, chaser);
+        assert_eq!(rect.y + rect.height, 350.0);
+        assert!(rect.x < 3.0 * 70.0);
+    }
+
+    #[test]
+    fn jumpers_jump_on_their_interval() {
+        let mut world = world();
+        let hopper = world.spawn("hopper", 700.0, 920.0).unwrap();
+        run(&mut world, 30);
+        let floor = rect(&world, hopper).y;
+
+        let mut highest = floor;
+        for _ in 0..90 {
+            run(&mut world, 1);
+            highest = f32::min(highest, rect(&world, hopper).y);
+        }
+
+        assert!(highest < floor);
+        assert_eq!(rect(&world, hopper).x, 700.0);
+    }
+}
@@ -0,0 +1,144 @@
+use coffee::input::keyboard::KeyCode;
+use coffee::input::KeyboardAndMouse;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Read};
+
+use crate::error::Error;
+
+/// Something the player can ask for, independent of the keys it's bound to.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    Run,
+    Pause,
+    QuickSave,
+    QuickLoad,
+}
+
+/// The actions being held down at a moment in time.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct Actions {
+    active: BTreeSet<Action>,
+}
+
+impl Actions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    pub fn with(mut self, action: Action) -> Self {
+        self.active.insert(action);
+        self
+    }
+
+    pub fn is_active(&self, action: Action) -> bool {
+        self.active.contains(&action)
+    }
+
+    /// Whether the action is held now but wasn't in `previous`.
+    pub fn just_pressed(&self, previous: &Actions, action: Action) -> bool {
+        self.is_active(action) && !previous.is_active(action)
+    }
+}
+
+/// The keys bound to each action, as read from `assets/bindings.json`.
+///
+/// Keys are named after their `KeyCode`s, such as `"A"`, `"Space"` or `"Key1"`, and any of
+/// the keys bound to an action will trigger it.
+pub struct Bindings {
+    bindings: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Bindings {
+    pub fn new(bindings: BTreeMap<Action, Vec<KeyCode>>) -> Self {
+        Self { bindings }
+    }
+
+    pub fn load() -> Result<Self, Error> {
+        let file = File::open("assets/bindings.json")?;
+        Self::read(io::BufReader::new(file))
+    }
+
+    /// Reads bindings laid out as in `assets/bindings.json`, turning their key names into keys.
+    pub fn read<R: Read>(reader: R) -> Result<Self, Error> {
+        let names: BTreeMap<Action, Vec<String>> = serde_json::from_reader(reader)?;
+
+        let mut bindings = BTreeMap::new();
+        for (action, keys) in names {
+            let keys = keys
+                .iter()
+                .map(|key| key_code(key))
+                .collect::<Result<Vec<KeyCode>, Error>>()?;
+            bindings.insert(action, keys);
+        }
+        Ok(Self::new(bindings))
+    }
+
+    /// The actions whose keys are currently pressed.
+    pub fn actions(&self, input: &KeyboardAndMouse) -> Actions {
+        Actions {
+            active: self
+                .bindings
+                .iter()
+                .filter(|(_, keys)| keys.iter().any(|key| input.is_key_pressed(*key)))
+                .map(|(action, _)| *action)
+                .collect(),
+        }
+    }
+}
+
+macro_rules! key_codes {
+    ($name:expr, $($key:ident),+) => {
+        match $name {
+            $(stringify!($key) => Ok(KeyCode::$key),)+
+            _ => Err(Error::UnknownKey($name.to_string())),
+        }
+    };
+}
+
+fn key_code(name: &str) -> Result<KeyCode, Error> {
+    key_codes!(
+        name, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Key0,
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Left, Right, Up, Down, Space, Return,
+        Escape, Tab, LShift, RShift, LControl, RControl, LAlt, RAlt, F1, F2, F3, F4, F5, F6, F7,
+        F8, F9, F10, F11, F12
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_shipped_bindings_are_read() {
+        let bindings = Bindings::read(include_str!("../assets/bindings.json").as_bytes()).unwrap();
+
+        assert_eq!(bindings.bindings.len(), 8);
+        assert_eq!(
+            bindings.bindings[&Action::Jump],
+            vec![KeyCode::W, KeyCode::Up, KeyCode::Space]
+        );
+        assert_eq!(
+            bindings.bindings[&Action::Run],
+            vec![KeyCode::LShift, KeyCode::RShift]
+        );
+        assert_eq!(bindings.bindings[&Action::QuickSave], vec![KeyCode::F5]);
+    }
+
+    #[test]
+    fn unknown_keys_are_errors() {
+        let json = r#"{ "Jump": ["Space", "Spacebar"] }"#;
+
+        match Bindings::read(json.as_bytes()) {
+            Err(Error::UnknownKey(key)) => assert_eq!(key, "Spacebar"),
+            Err(other) => panic!("expected an unknown key error, got {:?}", other),
+            Ok(_) => panic!("expected an unknown key error"),
+        }
+    }
+}
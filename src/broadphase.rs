@@ -0,0 +1,53 @@
+use crate::entity::Entity;
+use crate::rect::Rect;
+use std::collections::{BTreeSet, HashMap};
+
+/// Buckets rects into a uniform grid, so that only the rects sharing a grid cell need to be
+/// tested against each other.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, rect: &Rect<f32>) {
+        let minx = (rect.x / self.cell_size).floor() as i32;
+        let maxx = ((rect.x + rect.width) / self.cell_size).floor() as i32;
+        let miny = (rect.y / self.cell_size).floor() as i32;
+        let maxy = ((rect.y + rect.height) / self.cell_size).floor() as i32;
+
+        for x in minx..=maxx {
+            for y in miny..=maxy {
+                self.cells
+                    .entry((x, y))
+                    .or_insert_with(Vec::new)
+                    .push(entity);
+            }
+        }
+    }
+
+    /// Every pair of rects which share at least one grid cell, each reported once with the
+    /// lower id first.
+    pub fn pairs(&self) -> BTreeSet<(Entity, Entity)> {
+        let mut pairs = BTreeSet::new();
+        for entities in self.cells.values() {
+            for (i, a) in entities.iter().enumerate() {
+                for b in entities[i + 1..].iter() {
+                    if a < b {
+                        pairs.insert((*a, *b));
+                    } else if b < a {
+                        pairs.insert((*b, *a));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+}
@@ -1,20 +1,47 @@
 use crate::rect::Rect;
 use coffee::graphics::{Transformation, Vector};
+use nalgebra::{Point2, Vector2};
+
+/// How fast the target has to be moving, in pixels per second, for the camera to look ahead
+/// of it in that direction.
+const LOOK_AHEAD_SPEED: f32 = 10.0;
+
+/// How the camera catches up with its target once the target leaves the dead zone.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Follow {
+    /// Moves just far enough to put the target back in the dead zone straight away.
+    Snap,
+    /// Eases towards putting the target back in the dead zone. Each second, the camera closes
+    /// all but `e^-stiffness` of the distance left on each axis, so stiffer axes catch up
+    /// faster.
+    Damped { stiffness_x: f32, stiffness_y: f32 },
+}
 
 pub struct Camera {
     area: Rect<f32>,
-    margin: Rect<f32>,
+    /// The part of the view that the target can move around in without the camera following,
+    /// relative to the top left of the view.
+    dead_zone: Rect<f32>,
     max_bounds: Option<Rect<f32>>,
     zoom: f32,
+    follow: Follow,
+    /// How far ahead of the target the camera looks on each axis, in the direction it's moving.
+    look_ahead: Vector2<f32>,
+    lead: Vector2<f32>,
+    previous_target: Option<Point2<f32>>,
 }
 
 impl Camera {
     pub fn new(rect: Rect<f32>) -> Self {
         Self {
             area: rect,
-            margin: Rect::new(100.0, 100.0, 100.0, 100.0),
+            dead_zone: Rect::new(100.0, 100.0, rect.width - 200.0, rect.height - 200.0),
             max_bounds: None,
             zoom: 1.0,
+            follow: Follow::Snap,
+            look_ahead: Vector2::new(0.0, 0.0),
+            lead: Vector2::new(0.0, 0.0),
+            previous_target: None,
         }
     }
 
@@ -23,33 +50,60 @@ impl Camera {
         self
     }
 
+    pub fn with_follow(mut self, follow: Follow) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// Sets the part of the view the target can move around in without the camera following,
+    /// relative to the top left of the view.
+    pub fn with_dead_zone(mut self, dead_zone: Rect<f32>) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+
+    pub fn with_look_ahead(mut self, x: f32, y: f32) -> Self {
+        self.look_ahead = Vector2::new(x, y);
+        self
+    }
+
+    pub fn get_area(&self) -> &Rect<f32> {
+        &self.area
+    }
+
     fn get_offset(self: &Self, target: Option<&Rect<f32>>) -> (f32, f32) {
         let mut x = self.area.x;
         let mut y = self.area.y;
 
         // Adjust the position based on the target, if it exists.
         if let Some(target) = target {
-            let max_x_bound = target.x - self.margin.x;
+            let max_x_bound = target.x - self.dead_zone.x;
             if x > max_x_bound {
                 x = max_x_bound;
             }
 
-            let min_x_bound = target.x + target.width + self.margin.width - self.area.width;
+            let min_x_bound = target.x + target.width - self.dead_zone.x - self.dead_zone.width;
             if x < min_x_bound {
                 x = min_x_bound;
             }
 
-            let max_y_bound = target.y - self.margin.y;
+            let max_y_bound = target.y - self.dead_zone.y;
             if y > max_y_bound {
                 y = max_y_bound;
             }
 
-            let min_y_bound = target.y + target.height + self.margin.height - self.area.height;
+            let min_y_bound = target.y + target.height - self.dead_zone.y - self.dead_zone.height;
             if y < min_y_bound {
                 y = min_y_bound;
             }
         }
 
+        self.clamp_to_bounds(x, y, target)
+    }
+
+    /// Keeps the view within the bounds, centring it on the target along any axis where the
+    /// bounds are smaller than the view.
+    fn clamp_to_bounds(&self, mut x: f32, mut y: f32, target: Option<&Rect<f32>>) -> (f32, f32) {
         // Adjusts x and y relative to the maximum/minimum bounds.
         if let Some(max_bounds) = self.max_bounds {
             if x < max_bounds.x {
@@ -84,21 +138,179 @@ impl Camera {
         (x, y)
     }
 
-    pub fn get_transform(self: &Self, target: Option<&Rect<f32>>) -> Transformation {
-        let (x, y) = self.get_offset(target);
+    /// The transformation for the view where it was left by the last `update`.
+    pub fn get_transform(self: &Self) -> Transformation {
         Transformation::identity()
             * Transformation::scale(self.zoom)
-            * Transformation::translate(Vector::new(-1.0 * x, -1.0 * y))
+            * Transformation::translate(Vector::new(-1.0 * self.area.x, -1.0 * self.area.y))
     }
 
-    pub fn update(self: &mut Self, target: Option<&Rect<f32>>) -> Transformation {
-        let (x, y) = self.get_offset(target);
+    /// Moves the view after `elapsed` seconds of following the target.
+    pub fn update(self: &mut Self, target: Option<&Rect<f32>>, elapsed: f32) -> Transformation {
+        let target = target.map(|target| {
+            self.look_ahead_of(target, elapsed);
+            Rect::new(
+                target.x + self.lead.x,
+                target.y + self.lead.y,
+                target.width,
+                target.height,
+            )
+        });
+        let (x, y) = self.get_offset(target.as_ref());
+
+        let (x, y) = match self.follow {
+            Follow::Snap => (x, y),
+            Follow::Damped {
+                stiffness_x,
+                stiffness_y,
+            } => {
+                let x = ease(self.area.x, x, stiffness_x, elapsed);
+                let y = ease(self.area.y, y, stiffness_y, elapsed);
+                self.clamp_to_bounds(x, y, target.as_ref())
+            }
+        };
 
         self.area.x = x;
         self.area.y = y;
+        self.get_transform()
+    }
 
-        Transformation::identity()
-            * Transformation::scale(self.zoom)
-            * Transformation::translate(Vector::new(-1.0 * x as f32, -1.0 * y as f32))
+    /// Moves the lead towards the look ahead distance in whichever direction the target has
+    /// moved since the last update. While it's standing still on an axis, the lead is kept.
+    fn look_ahead_of(&mut self, target: &Rect<f32>, elapsed: f32) {
+        let centre = Point2::new(
+            target.x + target.width / 2.0,
+            target.y + target.height / 2.0,
+        );
+        let previous = self.previous_target.replace(centre);
+        if elapsed <= 0.0 {
+            return;
+        }
+
+        let velocity = previous.map_or(Vector2::new(0.0, 0.0), |previous| {
+            (centre - previous) / elapsed
+        });
+        let (stiffness_x, stiffness_y) = match self.follow {
+            Follow::Snap => (std::f32::INFINITY, std::f32::INFINITY),
+            Follow::Damped {
+                stiffness_x,
+                stiffness_y,
+            } => (stiffness_x, stiffness_y),
+        };
+
+        if velocity.x.abs() > LOOK_AHEAD_SPEED {
+            let lead = velocity.x.signum() * self.look_ahead.x;
+            self.lead.x = ease(self.lead.x, lead, stiffness_x, elapsed);
+        }
+        if velocity.y.abs() > LOOK_AHEAD_SPEED {
+            let lead = velocity.y.signum() * self.look_ahead.y;
+            self.lead.y = ease(self.lead.y, lead, stiffness_y, elapsed);
+        }
+    }
+}
+
+/// Moves from `from` towards `to` by the fraction that the stiffness closes in the time
+/// elapsed, which comes to the same place however the time is split between calls.
+fn ease(from: f32, to: f32, stiffness: f32, elapsed: f32) -> f32 {
+    let remaining = (-stiffness * elapsed).exp();
+    to + (from - to) * remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new(Rect::new(0.0, 0.0, 800.0, 600.0))
+            .with_bounds(Rect::new(0.0, 0.0, 4000.0, 4000.0))
+    }
+
+    fn target(x: f32, y: f32) -> Rect<f32> {
+        Rect::new(x, y, 50.0, 100.0)
+    }
+
+    #[test]
+    fn snapping_keeps_the_target_in_the_dead_zone() {
+        let mut camera = camera();
+        camera.update(Some(&target(2000.0, 300.0)), 0.0);
+
+        assert_eq!(camera.get_area().x, 2000.0 + 50.0 + 100.0 - 800.0);
+        assert_eq!(camera.get_area().y, 0.0);
+    }
+
+    #[test]
+    fn targets_move_freely_within_the_dead_zone() {
+        let mut camera = camera().with_follow(Follow::Damped {
+            stiffness_x: 5.0,
+            stiffness_y: 5.0,
+        });
+
+        for step in 0..10 {
+            let x = 200.0 + step as f32 * 20.0;
+            camera.update(Some(&target(x, 200.0)), 1.0 / 60.0);
+            assert_eq!((camera.get_area().x, camera.get_area().y), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn damping_is_independent_of_the_frame_rate() {
+        let follow = Follow::Damped {
+            stiffness_x: 4.0,
+            stiffness_y: 2.0,
+        };
+        let mut slow = camera().with_follow(follow);
+        let mut fast = camera().with_follow(follow);
+
+        for _ in 0..30 {
+            slow.update(Some(&target(2000.0, 2000.0)), 1.0 / 30.0);
+        }
+        for _ in 0..120 {
+            fast.update(Some(&target(2000.0, 2000.0)), 1.0 / 120.0);
+        }
+
+        assert!((slow.get_area().x - fast.get_area().x).abs() < 0.01);
+        assert!((slow.get_area().y - fast.get_area().y).abs() < 0.01);
+    }
+
+    #[test]
+    fn each_axis_has_its_own_stiffness() {
+        let mut camera = camera().with_follow(Follow::Damped {
+            stiffness_x: 8.0,
+            stiffness_y: 1.0,
+        });
+        let snapped = {
+            let mut snapped = self::camera();
+            snapped.update(Some(&target(2000.0, 2000.0)), 0.0);
+            *snapped.get_area()
+        };
+
+        camera.update(Some(&target(2000.0, 2000.0)), 0.25);
+        let x = camera.get_area().x / snapped.x;
+        let y = camera.get_area().y / snapped.y;
+        assert!(x > y && x < 1.0 && y > 0.0);
+    }
+
+    #[test]
+    fn looks_ahead_in_the_direction_of_movement() {
+        let mut still = camera();
+        let mut looking = camera().with_look_ahead(150.0, 0.0);
+
+        for step in 0..60 {
+            let moving = target(1000.0 + step as f32 * 5.0, 300.0);
+            still.update(Some(&moving), 1.0 / 60.0);
+            looking.update(Some(&moving), 1.0 / 60.0);
+        }
+        assert_eq!(looking.get_area().x, still.get_area().x + 150.0);
+
+        // Turning around shifts the view back, before the target is anywhere near the edge of
+        // the dead zone.
+        let (still_x, looking_x) = (still.get_area().x, looking.get_area().x);
+        for step in 0..60 {
+            let moving = target(1290.0 - step as f32 * 5.0, 300.0);
+            still.update(Some(&moving), 1.0 / 60.0);
+            looking.update(Some(&moving), 1.0 / 60.0);
+        }
+        assert_eq!(still.get_area().x, still_x);
+        assert!(looking.get_area().x < looking_x);
     }
 }
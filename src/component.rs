@@ -0,0 +1,325 @@
+use crate::entity::Entity;
+use std::any::{Any, TypeId};
+use std::collections::btree_map;
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+
+/// The components attached to entities, stored per type so that gameplay code can attach any
+/// type of component without it having to be declared up front.
+pub struct Components {
+    storages: HashMap<TypeId, Box<dyn Storage>>,
+}
+
+/// Any type which can be attached to an entity.
+pub trait Component: Any {}
+
+impl<T: Any> Component for T {}
+
+/// The storage for one type of component, with its type erased so that an entity can be
+/// removed from every storage at once.
+pub trait Storage: Any {
+    fn remove_entity(&mut self, entity: Entity);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> Storage for BTreeMap<Entity, T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(&entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Components {
+    pub fn new() -> Self {
+        Self {
+            storages: HashMap::new(),
+        }
+    }
+
+    /// Attaches a component to the entity, returning the component of the same type that it
+    /// replaced, if any.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+        self.storage_mut::<T>().insert(entity, component)
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>().and_then(|storage| storage.get(&entity))
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storage_mut::<T>().get_mut(&entity)
+    }
+
+    #[cfg(any(test, allow_unused))]
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_mut::<T>().remove(&entity)
+    }
+
+    /// Removes every component attached to the entity.
+    pub fn remove_entity(&mut self, entity: Entity) {
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+    }
+
+    pub fn contains<T: Component>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    /// Every component of the given type, ordered by the id of the entity it's attached to.
+    pub fn iter<T: Component>(&self) -> impl Iterator<Item = (&Entity, &T)> {
+        self.storage::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.iter())
+    }
+
+    pub fn iter_mut<T: Component>(&mut self) -> btree_map::IterMut<'_, Entity, T> {
+        self.storage_mut::<T>().iter_mut()
+    }
+
+    /// Every entity which has all of the components in the query, such as
+    /// `(&Object, &mut Movement)`, ordered by id.
+    ///
+    /// Each component type may only appear once in a query; a query which repeats a type
+    /// matches nothing.
+    pub fn query<'a, Q: Query<'a>>(&'a mut self) -> QueryIter<'a, Q> {
+        QueryIter {
+            iters: Q::iters(self),
+            query: PhantomData,
+        }
+    }
+
+    fn storage<T: Component>(&self) -> Option<&BTreeMap<Entity, T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref())
+    }
+
+    fn storage_mut<T: Component>(&mut self) -> &mut BTreeMap<Entity, T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(BTreeMap::<Entity, T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("component storage holds the wrong type")
+    }
+}
+
+/// A single component in a `Query`, fetched either shared (`&T`) or exclusively (`&mut T`).
+pub trait Fetch<'a> {
+    type Item;
+    type Iter: Iterator<Item = (&'a Entity, Self::Item)>;
+
+    fn type_id() -> TypeId;
+
+    fn iter(storage: &'a mut Box<dyn Storage>) -> Self::Iter;
+}
+
+impl<'a, T: Component> Fetch<'a> for &'a T {
+    type Item = &'a T;
+    type Iter = btree_map::Iter<'a, Entity, T>;
+
+    fn type_id() -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn iter(storage: &'a mut Box<dyn Storage>) -> Self::Iter {
+        let storage: &'a Box<dyn Storage> = storage;
+        storage
+            .as_any()
+            .downcast_ref::<BTreeMap<Entity, T>>()
+            .expect("component storage holds the wrong type")
+            .iter()
+    }
+}
+
+impl<'a, T: Component> Fetch<'a> for &'a mut T {
+    type Item = &'a mut T;
+    type Iter = btree_map::IterMut<'a, Entity, T>;
+
+    fn type_id() -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn iter(storage: &'a mut Box<dyn Storage>) -> Self::Iter {
+        storage
+            .as_any_mut()
+            .downcast_mut::<BTreeMap<Entity, T>>()
+            .expect("component storage holds the wrong type")
+            .iter_mut()
+    }
+}
+
+/// A tuple of components to fetch together for each entity which has all of them.
+///
+/// Every storage is kept ordered by entity id, so the entities are found by walking the
+/// storages side by side, without having to look each entity up in each storage.
+pub trait Query<'a> {
+    type Item;
+    type Iters;
+
+    /// The iterators over each of the storages, or `None` if any of them are missing.
+    fn iters(components: &'a mut Components) -> Option<Self::Iters>;
+
+    /// Advances the iterators to the next entity that all of them share.
+    fn next(iters: &mut Self::Iters) -> Option<(Entity, Self::Item)>;
+}
+
+pub struct QueryIter<'a, Q: Query<'a>> {
+    iters: Option<Q::Iters>,
+    query: PhantomData<Q>,
+}
+
+impl<'a, Q: Query<'a>> Iterator for QueryIter<'a, Q> {
+    type Item = (Entity, Q::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Q::next(self.iters.as_mut()?)
+    }
+}
+
+macro_rules! tuple_query {
+    ($($fetch:ident $iter:ident $current:ident),+) => {
+        impl<'a, $($fetch: Fetch<'a>),+> Query<'a> for ($($fetch,)+) {
+            type Item = ($($fetch::Item,)+);
+            type Iters = ($($fetch::Iter,)+);
+
+            fn iters(components: &'a mut Components) -> Option<Self::Iters> {
+                $(let mut $iter = None;)+
+                for (type_id, storage) in components.storages.iter_mut() {
+                    let mut storage = Some(storage);
+                    $(
+                        if *type_id == $fetch::type_id() {
+                            if let Some(storage) = storage.take() {
+                                $iter = Some($fetch::iter(storage));
+                            }
+                        }
+                    )+
+                }
+                Some(($($iter?,)+))
+            }
+
+            fn next(iters: &mut Self::Iters) -> Option<(Entity, Self::Item)> {
+                let ($($iter,)+) = iters;
+                $(let mut $current = $iter.next()?;)+
+                loop {
+                    let mut target = None;
+                    $(
+                        if target.map_or(true, |target| *$current.0 > target) {
+                            target = Some(*$current.0);
+                        }
+                    )+
+                    let target = target?;
+
+                    let mut aligned = true;
+                    $(
+                        while *$current.0 < target {
+                            $current = $iter.next()?;
+                        }
+                        aligned = aligned && *$current.0 == target;
+                    )+
+
+                    if aligned {
+                        return Some((target, ($($current.1,)+)));
+                    }
+                }
+            }
+        }
+    };
+}
+
+tuple_query!(A a_iter a);
+tuple_query!(A a_iter a, B b_iter b);
+tuple_query!(A a_iter a, B b_iter b, C c_iter c);
+tuple_query!(A a_iter a, B b_iter b, C c_iter c, D d_iter d);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{EntityBuilder, EntityManager};
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+
+    struct Health;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut entity_manager = EntityManager::new();
+        (0..count)
+            .map(|_| entity_manager.add(EntityBuilder::new()))
+            .collect()
+    }
+
+    #[test]
+    fn queries_mix_shared_and_exclusive_components() {
+        let entities = entities(2);
+        let mut components = Components::new();
+        for (i, entity) in entities.iter().enumerate() {
+            components.insert(*entity, Position(i as i32));
+            components.insert(*entity, Velocity(10));
+        }
+
+        for (_, (velocity, position)) in components.query::<(&Velocity, &mut Position)>() {
+            position.0 += velocity.0;
+        }
+
+        assert_eq!(components.get::<Position>(entities[0]), Some(&Position(10)));
+        assert_eq!(components.get::<Position>(entities[1]), Some(&Position(11)));
+        assert_eq!(components.get::<Velocity>(entities[1]), Some(&Velocity(10)));
+    }
+
+    #[test]
+    fn queries_on_a_missing_storage_match_nothing() {
+        let entities = entities(1);
+        let mut components = Components::new();
+        components.insert(entities[0], Position(0));
+
+        assert_eq!(components.query::<(&Position, &Health)>().count(), 0);
+        assert_eq!(components.query::<(&Position,)>().count(), 1);
+    }
+
+    #[test]
+    fn queries_skip_entities_missing_any_component() {
+        let entities = entities(5);
+        let mut components = Components::new();
+        components.insert(entities[0], Position(0));
+        components.insert(entities[0], Velocity(0));
+        components.insert(entities[1], Position(1));
+        components.insert(entities[2], Velocity(2));
+        components.insert(entities[3], Position(3));
+        components.insert(entities[3], Velocity(3));
+        components.insert(entities[4], Velocity(4));
+
+        let matched: Vec<(Entity, i32, i32)> = components
+            .query::<(&Position, &Velocity)>()
+            .map(|(entity, (position, velocity))| (entity, position.0, velocity.0))
+            .collect();
+        assert_eq!(matched, vec![(entities[0], 0, 0), (entities[3], 3, 3)]);
+    }
+
+    #[test]
+    fn queries_repeating_a_type_match_nothing() {
+        let entities = entities(1);
+        let mut components = Components::new();
+        components.insert(entities[0], Position(0));
+
+        assert_eq!(components.query::<(&Position, &Position)>().count(), 0);
+        assert_eq!(
+            components.query::<(&mut Position, &mut Position)>().count(),
+            0
+        );
+    }
+}
@@ -1,140 +1,808 @@
 // use crate::rect::Rect;
 // use nalgebra::Vector2;
-// use serde::{Deserialize, Serialize};
+use crate::acceleration::Acceleration;
+use crate::ai::Surroundings;
+use crate::broadphase::SpatialHash;
+use crate::component::{Component, Components, Query, QueryIter};
+use crate::error::Error;
+use crate::hierarchy::Parent;
 use crate::input::Input;
-use crate::map::Map;
-use crate::object::{Movement, Object};
+use crate::layers::Layers;
+use crate::map::{Cell, Map};
+use crate::navigation::{NavGraphs, Reach};
+use crate::object::{Collision, Movement, Object};
+use crate::platform::Platform;
+use crate::rect::Rect;
+use crate::surface::{Surface, Surfaces};
+use crate::trigger::{Trigger, TriggerEvent};
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::HashMap;
-use uuid::Uuid;
+use std::collections::vec_deque::Drain;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Mutex;
 
+/// A handle to an entity.
+///
+/// Handles are made up of the slot that the entity occupies and which occupant of that slot
+/// it is, so that a handle to a despawned entity is never mistaken for whatever entity reuses
+/// its slot. Slots are handed out in a fixed order, so the same sequence of spawns and
+/// despawns always produces the same handles.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct Entity {
-    pub id: Uuid,
+    index: u32,
+    generation: u32,
 }
 
-impl Entity {
-    pub fn new() -> Self {
-        Self { id: Uuid::new_v4() }
+/// Hands out entity handles, reusing the slots of despawned entities.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Allocator {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+}
+
+impl Allocator {
+    fn new() -> Self {
+        Self {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                self.alive.push(false);
+                self.generations.len() as u32 - 1
+            }
+        };
+
+        self.alive[index as usize] = true;
+        Entity {
+            index,
+            generation: self.generations[index as usize],
+        }
     }
+
+    fn deallocate(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let index = entity.index as usize;
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        self.generations.get(index) == Some(&entity.generation) && self.alive[index]
+    }
+}
+
+/// Notifications of entities being added to or removed from an `EntityManager`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EntityEvent {
+    Spawned(Entity),
+    Despawned(Entity),
 }
 
 pub struct EntityManager<'a> {
-    entities: HashMap<Uuid, Entity>,
-    names: HashMap<Cow<'a, str>, Uuid>,
-    objects: HashMap<Uuid, Object>,
-    assets: HashMap<Uuid, Cow<'a, str>>,
-    movements: HashMap<Uuid, Movement>,
-    inputs: HashMap<Uuid, Input>,
+    allocator: Allocator,
+    names: HashMap<Cow<'a, str>, Entity>,
+    tags: HashMap<Cow<'a, str>, BTreeSet<Entity>>,
+    assets: HashMap<Entity, Cow<'a, str>>,
+    components: Components,
+    trigger_events: VecDeque<TriggerEvent>,
+    entity_events: VecDeque<EntityEvent>,
+    pending_despawns: Mutex<Vec<Entity>>,
 }
 
 impl<'a> EntityManager<'a> {
     pub fn new() -> Self {
         Self {
-            entities: HashMap::new(),
+            allocator: Allocator::new(),
             names: HashMap::new(),
-            objects: HashMap::new(),
+            tags: HashMap::new(),
             assets: HashMap::new(),
-            movements: HashMap::new(),
-            inputs: HashMap::new(),
+            components: Components::new(),
+            trigger_events: VecDeque::new(),
+            entity_events: VecDeque::new(),
+            pending_despawns: Mutex::new(Vec::new()),
         }
     }
 
-    pub fn add(&mut self, entity_builder: EntityBuilder<'a>) {
-        let entity = entity_builder.entity;
-        let id = entity.id;
-
-        self.entities.insert(id, entity);
+    pub fn add(&mut self, entity_builder: EntityBuilder<'a>) -> Entity {
+        let id = self.allocator.allocate();
 
         if let Some(name) = entity_builder.name {
             self.names.insert(name, id);
         }
 
-        if let Some(object) = entity_builder.object {
-            self.objects.insert(id, object);
+        for tag in entity_builder.tags {
+            self.add_tag(id, tag);
         }
 
         if let Some(asset_name) = entity_builder.asset_name {
             self.assets.insert(id, asset_name);
         }
 
-        if let Some(movement) = entity_builder.movement {
-            self.movements.insert(id, movement);
+        for insert in entity_builder.components {
+            insert(&mut self.components, id);
+        }
+
+        if let Some((parent, offset)) = entity_builder.parent {
+            self.set_parent(id, parent, offset);
+        }
+
+        self.entity_events.push_back(EntityEvent::Spawned(id));
+        id
+    }
+
+    /// Removes the entity along with all of its components and children, returning whether it
+    /// existed.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.allocator.deallocate(entity) {
+            return false;
+        }
+
+        for child in self.children(entity) {
+            self.despawn(child);
         }
 
-        if let Some(input) = entity_builder.input {
-            self.inputs.insert(id, input);
+        self.names.retain(|_, id| *id != entity);
+        for tagged in self.tags.values_mut() {
+            tagged.remove(&entity);
+        }
+        self.assets.remove(&entity);
+        self.components.remove_entity(entity);
+        self.entity_events.push_back(EntityEvent::Despawned(entity));
+        true
+    }
+
+    /// Marks the entity to be despawned by the next `flush_despawns`.
+    ///
+    /// Unlike `despawn` this only needs a shared reference, so it can be called while iterating
+    /// over the entities.
+    pub fn queue_despawn(&self, entity: Entity) {
+        self.pending_despawns
+            .lock()
+            .expect("despawn queue was poisoned")
+            .push(entity);
+    }
+
+    /// Despawns every entity queued by `queue_despawn`.
+    pub fn flush_despawns(&mut self) {
+        let pending: Vec<Entity> = self
+            .pending_despawns
+            .get_mut()
+            .expect("despawn queue was poisoned")
+            .drain(..)
+            .collect();
+        for entity in pending {
+            self.despawn(entity);
         }
     }
 
-    pub fn by_name<S>(&self, name: S) -> Uuid
+    /// Takes the spawn and despawn events which have happened since they were last drained.
+    pub fn drain_entity_events(&mut self) -> Drain<'_, EntityEvent> {
+        self.entity_events.drain(..)
+    }
+
+    pub fn by_name<S>(&self, name: S) -> Option<Entity>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        self.names.get(&name.into()).cloned()
+    }
+
+    /// Adds the tag to the entity, returning false if the entity has been despawned.
+    pub fn add_tag<S>(&mut self, entity: Entity, tag: S) -> bool
     where
         S: Into<Cow<'a, str>>,
     {
-        self.names.get(&name.into()).unwrap_or(&Uuid::nil()).clone()
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        self.tags
+            .entry(tag.into())
+            .or_insert_with(BTreeSet::new)
+            .insert(entity);
+        true
+    }
+
+    /// Removes the tag from the entity, returning whether it had it.
+    pub fn remove_tag(&mut self, entity: Entity, tag: &str) -> bool {
+        self.tags
+            .get_mut(tag)
+            .map_or(false, |tagged| tagged.remove(&entity))
+    }
+
+    pub fn has_tag(&self, entity: Entity, tag: &str) -> bool {
+        self.tags
+            .get(tag)
+            .map_or(false, |tagged| tagged.contains(&entity))
+    }
+
+    /// Every entity with the tag, ordered by id.
+    pub fn tagged(&self, tag: &str) -> impl Iterator<Item = Entity> + '_ {
+        self.tags
+            .get(tag)
+            .into_iter()
+            .flat_map(|tagged| tagged.iter().cloned())
+    }
+
+    /// Attaches the entity to a parent, placing its object at the offset from the parent's.
+    /// Returns false if either of them has been despawned, or if the parent is the entity
+    /// itself or one of its descendants.
+    pub fn set_parent(&mut self, entity: Entity, parent: Entity, offset: Vector2<f32>) -> bool {
+        if !self.is_alive(parent) || self.ancestors(parent).any(|ancestor| ancestor == entity) {
+            return false;
+        }
+
+        self.insert(entity, Parent::new(parent, offset))
+    }
+
+    pub fn get_parent(&self, entity: Entity) -> Option<Entity> {
+        self.components
+            .get::<Parent>(entity)
+            .map(|parent| parent.entity)
+    }
+
+    /// The entities directly attached to the entity, ordered by id.
+    pub fn children(&self, entity: Entity) -> Vec<Entity> {
+        self.components
+            .iter::<Parent>()
+            .filter(|(_, parent)| parent.entity == entity)
+            .map(|(child, _)| *child)
+            .collect()
+    }
+
+    /// The entity itself, then its parent, its parent's parent, and so on.
+    fn ancestors(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+        let mut next = Some(entity);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = self.get_parent(current);
+            Some(current)
+        })
+    }
+
+    /// Moves every child's object to its place relative to its parent's.
+    ///
+    /// Each child is placed by adding up the offsets along the chain of its ancestors, so it
+    /// doesn't matter whether its parent has been placed yet this tick.
+    pub fn update_hierarchy(&mut self) {
+        let children: Vec<Entity> = self
+            .components
+            .iter::<Parent>()
+            .map(|(child, _)| *child)
+            .collect();
+
+        let depth = children.len();
+        for child in children {
+            let mut offset = Vector2::new(0.0, 0.0);
+            let mut root = child;
+            // A chain longer than there are children must loop back on itself.
+            for _ in 0..depth {
+                match self.components.get::<Parent>(root) {
+                    Some(parent) => {
+                        offset += parent.offset;
+                        root = parent.entity;
+                    }
+                    None => break,
+                }
+            }
+
+            let origin = match self.components.get::<Object>(root) {
+                Some(object) => object.rect.point(),
+                None => continue,
+            };
+            if let Some(object) = self.components.get_mut::<Object>(child) {
+                object.rect.x = origin.x + offset.x;
+                object.rect.y = origin.y + offset.y;
+            }
+        }
+    }
+
+    /// Whether the handle refers to an entity which hasn't been despawned.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.allocator.is_alive(entity)
+    }
+
+    /// Attaches a component of any type to the entity, replacing any it already had of the
+    /// same type. Returns false, discarding the component, if the entity has been despawned.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        self.components.insert(entity, component);
+        true
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.components.get(entity)
     }
 
-    pub fn get_entities(&self) -> Vec<Uuid> {
-        self.objects.keys().map(|uuid| *uuid).collect()
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.components.get_mut(entity)
     }
 
-    pub fn get_object(&self, uuid: Uuid) -> Option<&Object> {
-        self.objects.get(&uuid)
+    #[cfg(any(test, allow_unused))]
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.components.remove(entity)
     }
 
-    pub fn get_object_mut(&mut self, uuid: Uuid) -> Option<&mut Object> {
-        self.objects.get_mut(&uuid)
+    /// Every component of the given type, ordered by the id of the entity it's attached to.
+    pub fn iter<T: Component>(&self) -> impl Iterator<Item = (&Entity, &T)> {
+        self.components.iter()
     }
 
-    pub fn get_objects(&self) -> Vec<(&Uuid, &Object)> {
-        self.objects.iter().collect()
+    pub fn iter_mut<T: Component>(&mut self) -> impl Iterator<Item = (&Entity, &mut T)> {
+        self.components.iter_mut()
     }
 
-    pub fn get_asset(&mut self, uuid: Uuid) -> Option<&Cow<'a, str>> {
-        self.assets.get(&uuid)
+    /// Every entity which has all of the components in the query, such as
+    /// `(&Object, &mut Movement)`.
+    pub fn query<'q, Q: Query<'q>>(&'q mut self) -> QueryIter<'q, Q> {
+        self.components.query()
     }
 
-    pub fn get_assets(&self) -> Vec<(Uuid, Cow<'a, str>)> {
-        self.assets
+    /// Like `query`, but only the entities with the tag.
+    pub fn query_tagged<'q, Q: Query<'q>>(
+        &'q mut self,
+        tag: &str,
+    ) -> impl Iterator<Item = (Entity, Q::Item)> + 'q
+    where
+        Q: 'q,
+    {
+        let tagged = self.tags.get(tag);
+        self.components
+            .query::<Q>()
+            .filter(move |(entity, _)| tagged.map_or(false, |tagged| tagged.contains(entity)))
+    }
+
+    pub fn get_entities(&self) -> Vec<Entity> {
+        self.components
+            .iter::<Object>()
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    pub fn get_object(&self, entity: Entity) -> Option<&Object> {
+        self.get(entity)
+    }
+
+    pub fn get_object_mut(&mut self, entity: Entity) -> Option<&mut Object> {
+        self.get_mut(entity)
+    }
+
+    pub fn get_asset(&mut self, entity: Entity) -> Option<&Cow<'a, str>> {
+        self.assets.get(&entity)
+    }
+
+    pub fn get_assets(&self) -> impl Iterator<Item = (&Entity, &Cow<'a, str>)> {
+        self.assets.iter()
+    }
+
+    pub fn get_movement_mut(&mut self, entity: Entity) -> Option<&mut Movement> {
+        self.get_mut(entity)
+    }
+
+    pub fn get_input(&self, entity: Entity) -> &Input {
+        self.get(entity).unwrap_or(&Input::None)
+    }
+
+    /// A copy of every entity and its components, to be written to a save file.
+    ///
+    /// Only the built-in component types are saved; any other components are left out.
+    pub fn save(&self) -> SavedEntities<'a> {
+        let mut assets: Vec<(Entity, Cow<'a, str>)> = self
+            .assets
             .iter()
-            .map(|(uuid, name)| (uuid.clone(), name.clone()))
+            .map(|(entity, asset)| (*entity, asset.clone()))
+            .collect();
+        assets.sort_by_key(|(entity, _)| *entity);
+
+        SavedEntities {
+            allocator: self.allocator.clone(),
+            names: self
+                .names
+                .iter()
+                .map(|(name, entity)| (name.clone(), *entity))
+                .collect(),
+            tags: self
+                .tags
+                .iter()
+                .filter(|(_, tagged)| !tagged.is_empty())
+                .map(|(tag, tagged)| (tag.clone(), tagged.clone()))
+                .collect(),
+            assets,
+            objects: self.saved_components(),
+            movements: self.saved_components(),
+            inputs: self.saved_components(),
+            platforms: self.saved_components(),
+            triggers: self.saved_components(),
+            parents: self.saved_components(),
+        }
+    }
+
+    /// Rebuilds the entities from a save, with the same handles they had when it was made.
+    pub fn restore(saved: SavedEntities<'a>) -> Self {
+        let mut entity_manager = Self::new();
+        entity_manager.allocator = saved.allocator;
+        entity_manager.names = saved.names.into_iter().collect();
+        entity_manager.tags = saved.tags.into_iter().collect();
+        entity_manager.assets = saved.assets.into_iter().collect();
+        entity_manager.restore_components(saved.objects);
+        entity_manager.restore_components(saved.movements);
+        entity_manager.restore_components(saved.inputs);
+        entity_manager.restore_components(saved.platforms);
+        entity_manager.restore_components(saved.triggers);
+        entity_manager.restore_components(saved.parents);
+        entity_manager
+    }
+
+    /// Looks up the bits for any layer names in the entities' objects, so they're ready to be
+    /// collided.
+    pub fn resolve_layers(&mut self, layers: &Layers) -> Result<(), Error> {
+        for (_, object) in self.components.iter_mut::<Object>() {
+            object.resolve_layers(layers)?;
+        }
+        Ok(())
+    }
+
+    fn saved_components<T: Component + Clone>(&self) -> Vec<(Entity, T)> {
+        self.components
+            .iter::<T>()
+            .map(|(entity, component)| (*entity, component.clone()))
             .collect()
     }
 
-    pub fn get_movements_mut(&mut self) -> Vec<(&Uuid, &mut Movement)> {
-        self.movements.iter_mut().collect()
+    fn restore_components<T: Component>(&mut self, components: Vec<(Entity, T)>) {
+        for (entity, component) in components {
+            self.insert(entity, component);
+        }
     }
 
-    pub fn get_movement_mut(&mut self, uuid: Uuid) -> Option<&mut Movement> {
-        self.movements.get_mut(&uuid)
+    /// Moves every platform along its path, carrying the entities standing on top of it and
+    /// pushing aside any that it runs into.
+    pub fn update_platforms(&mut self, map: &Map) {
+        let ids: Vec<Entity> = self
+            .components
+            .iter::<Platform>()
+            .map(|(entity, _)| *entity)
+            .collect();
+        let movers: Vec<Entity> = self
+            .components
+            .iter::<Movement>()
+            .map(|(entity, _)| *entity)
+            .collect();
+
+        for id in ids {
+            let mut platform = match self.components.get::<Object>(id) {
+                Some(object) => object.clone(),
+                None => continue,
+            };
+            let before = platform.rect;
+            if let Some(path) = self.components.get_mut::<Platform>(id) {
+                let delta = path.step(before.point());
+                platform.move_by(delta.x, delta.y);
+            }
+
+            if platform.rect == before {
+                continue;
+            }
+
+            let riders: Vec<Entity> = movers
+                .iter()
+                .filter(|entity| match self.components.get::<Object>(**entity) {
+                    Some(object) => is_riding(&object.rect, &before),
+                    None => false,
+                })
+                .cloned()
+                .collect();
+
+            // Anything in the platform's way is pushed along, or stops it short.
+            for entity in movers.iter().filter(|entity| !riders.contains(entity)) {
+                if let Some(object) = self.components.get_mut::<Object>(*entity) {
+                    push_aside(object, &mut platform, map);
+                }
+            }
+
+            // Riders are carried as far as the platform actually went, which is no higher than
+            // the lowest ceiling over any of them allows.
+            let dx = platform.rect.x - before.x;
+            let mut dy = platform.rect.y - before.y;
+            if dy < 0.0 {
+                for entity in riders.iter() {
+                    if let Some(object) = self.components.get::<Object>(*entity) {
+                        let mut carried = object.clone();
+                        move_against_tiles(&mut carried, 0.0, dy, map);
+                        dy = f32::max(dy, carried.rect.y - object.rect.y);
+                    }
+                }
+                platform.rect.y = before.y + dy;
+            }
+
+            for entity in riders.iter() {
+                if let Some(object) = self.components.get_mut::<Object>(*entity) {
+                    move_against_tiles(object, dx, dy, map);
+                }
+            }
+
+            if let Some(object) = self.components.get_mut::<Object>(id) {
+                object.rect = platform.rect;
+            }
+        }
+    }
+
+    /// Checks which entities overlap each trigger, replacing the previous tick's trigger events
+    /// with the ones for this tick.
+    pub fn update_triggers(&mut self) {
+        self.trigger_events.clear();
+
+        let ids: BTreeSet<Entity> = self
+            .components
+            .iter::<Trigger>()
+            .map(|(entity, _)| *entity)
+            .collect();
+        for id in ids.iter() {
+            let volume = match self.components.get::<Object>(*id) {
+                Some(object) => object,
+                None => continue,
+            };
+            let tag = self
+                .components
+                .get::<Trigger>(*id)
+                .and_then(Trigger::get_tag);
+
+            let overlapping: BTreeSet<Entity> = self
+                .components
+                .iter::<Object>()
+                .filter(|(entity, object)| {
+                    !ids.contains(entity)
+                        && tag.map_or(true, |tag| self.has_tag(**entity, tag))
+                        && object.intersects(volume)
+                })
+                .map(|(entity, _)| *entity)
+                .collect();
+
+            if let Some(trigger) = self.components.get_mut::<Trigger>(*id) {
+                self.trigger_events.extend(trigger.update(*id, overlapping));
+            }
+        }
     }
 
-    pub fn get_inputs(&self) -> Vec<(&Uuid, &Input)> {
-        self.inputs.iter().collect()
+    /// Takes the trigger events from the most recent `update_triggers`.
+    pub fn drain_trigger_events(&mut self) -> Drain<'_, TriggerEvent> {
+        self.trigger_events.drain(..)
     }
 
-    pub fn get_inputs_mut(&mut self) -> Vec<(&Uuid, &mut Input)> {
-        self.inputs.iter_mut().collect()
+    /// Lets each AI controlled entity look at its surroundings and decide where to go. Chasers
+    /// are also given the navigation graph for their reach, moving at walking speed.
+    pub fn update_ai(
+        &mut self,
+        map: &Map,
+        navigation: &mut NavGraphs,
+        acceleration: &Acceleration,
+    ) {
+        let ids: Vec<Entity> = self
+            .components
+            .iter::<Input>()
+            .filter(|(_, input)| input.is_ai())
+            .map(|(entity, _)| *entity)
+            .collect();
+        for id in ids {
+            let rect = match self.components.get::<Object>(id) {
+                Some(object) => object.rect,
+                None => continue,
+            };
+            let grounded = self
+                .components
+                .get::<Movement>(id)
+                .map_or(false, Movement::is_grounded);
+            let (target, graph) = match self.components.get::<Input>(id) {
+                Some(Input::Chase(chase)) => (
+                    self.nearest_tagged(id, chase.get_target(), &rect),
+                    self.components.get::<Movement>(id).map(|movement| {
+                        let reach = Reach::new(
+                            rect.height,
+                            movement,
+                            acceleration.walk_speed,
+                            map.get_tilesize(),
+                        );
+                        navigation.get(map, reach)
+                    }),
+                ),
+                _ => (None, None),
+            };
+
+            let surroundings = Surroundings {
+                rect,
+                grounded,
+                target,
+                map,
+                graph,
+            };
+            if let Some(input) = self.components.get_mut::<Input>(id) {
+                input.think(&surroundings);
+            }
+        }
     }
 
-    pub fn get_input(&self, uuid: Uuid) -> &Input {
-        self.inputs.get(&uuid).unwrap_or(&Input::None)
+    /// The rect of the entity with the tag closest to the rect, other than the entity itself.
+    fn nearest_tagged(&self, entity: Entity, tag: &str, rect: &Rect<f32>) -> Option<Rect<f32>> {
+        let centre =
+            |rect: &Rect<f32>| Vector2::new(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+        self.tagged(tag)
+            .filter(|other| *other != entity)
+            .filter_map(|other| self.components.get::<Object>(other))
+            .map(|object| (object.rect, (centre(&object.rect) - centre(rect)).norm()))
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(rect, _)| rect)
     }
 
-    pub fn update(&mut self, uuid: Uuid, map: &Map) {
-        let mut dx = 0.0;
-        let mut dy = 0.0;
-        if let Some(movement) = self.movements.get_mut(&uuid) {
-            dx = movement.dx();
-            dy = movement.dy();
+    /// Pushes apart any solid entities which ended up overlapping each other.
+    ///
+    /// When both entities can move they're each pushed half of the way, otherwise the one which
+    /// can move is pushed clear of the one which can't. Platforms and triggers are left to
+    /// `update_platforms` and `update_triggers`.
+    pub fn resolve_entity_collisions(&mut self, map: &Map) {
+        let mut spatial_hash = SpatialHash::new(map.get_tilesize());
+        for (entity, object) in self.components.iter::<Object>() {
+            if object.get_collision() == Collision::Solid
+                && !self.components.contains::<Platform>(*entity)
+                && !self.components.contains::<Trigger>(*entity)
+                && !self.components.contains::<Parent>(*entity)
+            {
+                spatial_hash.insert(*entity, &object.rect);
+            }
         }
 
+        for (a, b) in spatial_hash.pairs() {
+            let a_moves = self.components.contains::<Movement>(a);
+            let b_moves = self.components.contains::<Movement>(b);
+            if !a_moves && !b_moves {
+                continue;
+            }
+
+            let (a_object, b_object) = match (
+                self.components.get::<Object>(a),
+                self.components.get::<Object>(b),
+            ) {
+                (Some(a_object), Some(b_object)) => (a_object, b_object),
+                _ => continue,
+            };
+
+            let overlap = match a_object.overlap(b_object) {
+                Some(overlap) if overlap.width > 0.01 && overlap.height > 0.01 => overlap,
+                _ => continue,
+            };
+
+            // Push `a` away from `b` along whichever axis they're least embedded in.
+            let (px, py) = if overlap.width < overlap.height {
+                let direction = (a_object.rect.x - b_object.rect.x).signum();
+                (direction * overlap.width, 0.0)
+            } else {
+                let direction = (a_object.rect.y - b_object.rect.y).signum();
+                (0.0, direction * overlap.height)
+            };
+            // Each is pushed half of the way, unless the other can't move. Whatever part of
+            // its push `a` is stopped from making by the tiles, `b` is pushed on by instead,
+            // and the other way around.
+            let share = if a_moves && b_moves { 0.5 } else { 1.0 };
+            let (mut rx, mut ry) = (px, py);
+            if b_moves {
+                let (dx, dy) = self.push(b, -px * share, -py * share, map);
+                rx += dx;
+                ry += dy;
+            }
+            if a_moves {
+                let (dx, dy) = self.push(a, rx, ry, map);
+                rx -= dx;
+                ry -= dy;
+                if b_moves {
+                    self.push(b, -rx, -ry, map);
+                }
+            }
+
+            if b_moves {
+                self.settle(b, px != 0.0, -py);
+            }
+            if a_moves {
+                self.settle(a, px != 0.0, py);
+            }
+        }
+    }
+
+    /// Moves an entity's object as far as the tiles let it, returning how far it went.
+    fn push(&mut self, entity: Entity, dx: f32, dy: f32, map: &Map) -> (f32, f32) {
+        match self.components.get_mut::<Object>(entity) {
+            Some(object) => {
+                let before = object.rect;
+                move_against_tiles(object, dx, dy, map);
+                (object.rect.x - before.x, object.rect.y - before.y)
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Stops an entity that has been pushed out of another along the axis it was pushed on. An
+    /// entity pushed up while falling has landed on top of the other one.
+    fn settle(&mut self, entity: Entity, horizontal: bool, push_y: f32) {
+        if let Some(movement) = self.components.get_mut::<Movement>(entity) {
+            if push_y < 0.0 && movement.get_speed().y >= 0.0 {
+                movement.set_grounded(true);
+                movement.set_surface(Some(Surface::default()));
+            }
+            movement.stop(horizontal, push_y != 0.0);
+        }
+    }
+
+    /// A copy of every platform's object, taken once they've moved for the tick, for `update`
+    /// to collide entities against.
+    pub fn platforms(&self) -> Vec<(Entity, Object)> {
+        self.components
+            .iter::<Platform>()
+            .filter_map(|(id, _)| {
+                self.components
+                    .get::<Object>(*id)
+                    .map(|object| (*id, object.clone()))
+            })
+            .collect()
+    }
+
+    /// Moves the entity by its speed, stopping it against the map's tiles and the `platforms`.
+    pub fn update(
+        &mut self,
+        entity: Entity,
+        map: &Map,
+        surfaces: &Surfaces,
+        platforms: &[(Entity, Object)],
+    ) {
+        let (dx, dy, was_grounded) = match self.components.get_mut::<Movement>(entity) {
+            Some(movement) => (movement.dx(), movement.dy(), movement.is_grounded()),
+            None => return,
+        };
+
+        // Holding crouch drops the entity through any one-way platforms beneath it.
+        let dropping = self.get_input(entity).is_crouched();
+
         let mut hitx = false;
         let mut hity = false;
-        if let Some(object) = self.get_object_mut(uuid) {
+        let mut landed = false;
+        let mut surface = None;
+        if let Some(object) = self.get_object_mut(entity) {
+            // A grounded entity may step up small ledges, such as the lip where the top of a
+            // slope meets the next tile, rather than being stopped by them.
+            let step_height = if was_grounded {
+                object.rect.width / 2.0
+            } else {
+                0.0
+            };
+
             object.move_by(dx, 0.0);
             if dx != 0.0 {
-                for cell in map.collidable_tiles(&object.rect) {
-                    if let Some(overlap) = object.overlap(&cell.object) {
+                for other in colliders(map, &object.rect, platforms, entity) {
+                    if other.get_collision() == Collision::OneWayUp || other.get_shape().is_slope()
+                    {
+                        continue;
+                    }
+
+                    if let Some(overlap) = object.overlap(other) {
+                        if overlap.height <= step_height {
+                            continue;
+                        }
+
                         hitx = true;
                         if dx > 0.0 {
                             object.move_by(-overlap.width, 0.0);
@@ -145,12 +813,32 @@ impl<'a> EntityManager<'a> {
                 }
             }
 
+            let previous_top = object.rect.y;
+            let previous_bottom = object.rect.y + object.rect.height;
             object.move_by(0.0, dy);
             if dy != 0.0 {
-                for cell in map.collidable_tiles(&object.rect) {
-                    if let Some(overlap) = object.overlap(&cell.object) {
+                for other in colliders(map, &object.rect, platforms, entity) {
+                    let other_rect = other.collision_rect();
+                    if other.get_collision() == Collision::OneWayUp {
+                        // One-way platforms only catch entities falling onto them from above.
+                        let was_above = previous_bottom <= other_rect.y + 0.01;
+                        if dy < 0.0 || !was_above || dropping {
+                            continue;
+                        }
+                    }
+
+                    if other.get_shape().is_slope() {
+                        // Slopes are only solid from below here; their surface is handled next.
+                        let was_below = previous_top >= other_rect.y + other_rect.height - 0.01;
+                        if dy > 0.0 || !was_below {
+                            continue;
+                        }
+                    }
+
+                    if let Some(overlap) = object.overlap(other) {
                         hity = true;
                         if dy > 0.0 {
+                            landed = true;
                             object.move_by(0.0, -overlap.height);
                         } else {
                             object.move_by(0.0, overlap.height);
@@ -158,34 +846,213 @@ impl<'a> EntityManager<'a> {
                     }
                 }
             }
+
+            // Slopes are resolved against the point under the middle of the entity's feet, so
+            // it walks smoothly along them. An entity that was already on the ground is also
+            // pulled down onto the slope, so it follows the hill down rather than bouncing off.
+            // It may be pulled down as far as it could step up, since the edge of the tile at
+            // the top of the hill holds it up until its feet are that far down the slope, and
+            // onto solid ground too, so it doesn't drop off the bottom of the hill.
+            let foot_x = object.rect.x + object.rect.width / 2.0;
+            let top = object.rect.y;
+            let snap = if was_grounded && dy >= 0.0 {
+                step_height + dx.abs() + 1.0
+            } else {
+                0.0
+            };
+            let probe = Rect::new(foot_x - 0.5, top, 1.0, object.rect.height + snap);
+            let floor = map
+                .collidable_tiles(&probe)
+                .iter()
+                .filter(|cell| match cell.object.get_collision() {
+                    Collision::None => false,
+                    Collision::Solid => cell.object.get_shape().is_slope() || snap > 0.0,
+                    Collision::OneWayUp => cell.object.get_shape().is_slope(),
+                })
+                .map(|cell| cell.object.floor_at(foot_x))
+                .filter(|y| *y >= top)
+                .fold(None, |floor: Option<f32>, y| match floor {
+                    Some(floor) => Some(f32::min(floor, y)),
+                    None => Some(y),
+                });
+
+            if let Some(floor) = floor {
+                let bottom = object.rect.y + object.rect.height;
+                if bottom > floor || floor - bottom <= snap {
+                    object.move_by(0.0, floor - bottom);
+                    hity = true;
+                    landed = true;
+                }
+            }
+
+            if landed {
+                surface = Some(surface_under(map, &object.rect, surfaces));
+            }
         }
 
-        if hitx || hity {
-            if let Some(movement) = self.movements.get_mut(&uuid) {
-                movement.reset_speed();
+        if let Some(movement) = self.components.get_mut::<Movement>(entity) {
+            movement.set_grounded(landed);
+            movement.set_surface(surface);
+            movement.end_tick();
+            match surface {
+                Some(surface) if surface.restitution > 0.0 => {
+                    movement.bounce(surface.restitution);
+                    movement.stop(hitx, false);
+                }
+                _ => movement.stop(hitx, hity),
             }
         }
     }
 }
 
-pub struct EntityBuilder<'a> {
+/// The map tiles and platforms, other than the entity itself, that could collide with the
+/// target rect. Platforms are collided against just like the map's tiles.
+fn colliders<'m>(
+    map: &'m Map,
+    target: &Rect<f32>,
+    platforms: &'m [(Entity, Object)],
     entity: Entity,
+) -> Vec<&'m Object> {
+    map.collidable_tiles(target)
+        .into_iter()
+        .map(|cell| &cell.object)
+        .chain(
+            platforms
+                .iter()
+                .filter(|(id, _)| *id != entity)
+                .map(|(_, object)| object),
+        )
+        .collect()
+}
+
+/// Whether an object is standing on top of the target rect.
+fn is_riding(rect: &Rect<f32>, target: &Rect<f32>) -> bool {
+    let bottom = rect.y + rect.height;
+    (bottom - target.y).abs() <= 1.0
+        && rect.x < target.x + target.width
+        && target.x < rect.x + rect.width
+}
+
+/// Pushes the object out of the platform along whichever axis it's least embedded in, moving
+/// the platform back instead wherever a wall stops the object.
+fn push_aside(object: &mut Object, platform: &mut Object, map: &Map) {
+    let overlap = match object.overlap(platform) {
+        Some(overlap) => overlap,
+        None => return,
+    };
+
+    let push_x = overlap.width < overlap.height;
+    let (px, py) = if push_x {
+        let direction = (object.rect.x - platform.rect.x).signum();
+        (direction * overlap.width, 0.0)
+    } else {
+        let direction = (object.rect.y - platform.rect.y).signum();
+        (0.0, direction * overlap.height)
+    };
+    move_against_tiles(object, px, py, map);
+
+    // Rather than crushing the object through a wall, the platform stops short.
+    if let Some(overlap) = object.overlap(platform) {
+        if push_x {
+            platform.move_by(-px.signum() * overlap.width, 0.0);
+        } else {
+            platform.move_by(0.0, -py.signum() * overlap.height);
+        }
+    }
+}
+
+/// The surface of the tile under the middle of the rect's feet.
+fn surface_under(map: &Map, rect: &Rect<f32>, surfaces: &Surfaces) -> Surface {
+    let feet = Rect::new(rect.x, rect.y + rect.height, rect.width, 1.0);
+    let foot_x = rect.x + rect.width / 2.0;
+    let cells: Vec<&Cell> = map
+        .collidable_tiles(&feet)
+        .into_iter()
+        .filter(|cell| cell.object.get_collision() != Collision::None)
+        .collect();
+
+    let cell = cells
+        .iter()
+        .find(|cell| {
+            let cell_rect = cell.get_rect();
+            cell_rect.x <= foot_x && foot_x < cell_rect.x + cell_rect.width
+        })
+        .or_else(|| cells.first());
+    surfaces.get(
+        cell.and_then(|cell| cell.get_name())
+            .map(|name| name.as_ref()),
+    )
+}
+
+fn is_wall(object: &Object) -> bool {
+    object.get_collision() == Collision::Solid && !object.get_shape().is_slope()
+}
+
+/// Moves an object by the given amount, stopping it short of any solid tiles in the way.
+fn move_against_tiles(object: &mut Object, dx: f32, dy: f32, map: &Map) {
+    object.move_by(dx, 0.0);
+    for cell in map.collidable_tiles(&object.rect) {
+        if !is_wall(&cell.object) {
+            continue;
+        }
+
+        if let Some(overlap) = object.overlap(&cell.object) {
+            if overlap.height > 0.01 {
+                object.move_by(-dx.signum() * overlap.width, 0.0);
+            }
+        }
+    }
+
+    object.move_by(0.0, dy);
+    for cell in map.collidable_tiles(&object.rect) {
+        if !is_wall(&cell.object) {
+            continue;
+        }
+
+        if let Some(overlap) = object.overlap(&cell.object) {
+            if overlap.width > 0.01 {
+                object.move_by(0.0, -dy.signum() * overlap.height);
+            }
+        }
+    }
+}
+
+/// The state of an `EntityManager` as written to a save file.
+///
+/// Components are listed alongside their entity rather than keyed by it, since save files
+/// can only key maps by strings.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedEntities<'a> {
+    allocator: Allocator,
+    names: BTreeMap<Cow<'a, str>, Entity>,
+    #[serde(default)]
+    tags: BTreeMap<Cow<'a, str>, BTreeSet<Entity>>,
+    assets: Vec<(Entity, Cow<'a, str>)>,
+    objects: Vec<(Entity, Object)>,
+    movements: Vec<(Entity, Movement)>,
+    inputs: Vec<(Entity, Input)>,
+    platforms: Vec<(Entity, Platform)>,
+    triggers: Vec<(Entity, Trigger)>,
+    #[serde(default)]
+    parents: Vec<(Entity, Parent)>,
+}
+
+pub struct EntityBuilder<'a> {
     name: Option<Cow<'a, str>>,
+    tags: Vec<Cow<'a, str>>,
     asset_name: Option<Cow<'a, str>>,
-    object: Option<Object>,
-    movement: Option<Movement>,
-    input: Option<Input>,
+    components: Vec<Box<dyn FnOnce(&mut Components, Entity)>>,
+    parent: Option<(Entity, Vector2<f32>)>,
 }
 
 impl<'a> EntityBuilder<'a> {
     pub fn new() -> Self {
         Self {
-            entity: Entity::new(),
             name: None,
+            tags: Vec::new(),
             asset_name: None,
-            object: None,
-            movement: None,
-            input: None,
+            components: Vec::new(),
+            parent: None,
         }
     }
 
@@ -197,6 +1064,14 @@ impl<'a> EntityBuilder<'a> {
         self
     }
 
+    pub fn with_tag<S>(mut self, tag: S) -> Self
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        self.tags.push(tag.into());
+        self
+    }
+
     pub fn with_asset<S>(mut self, asset_name: S) -> Self
     where
         S: Into<Cow<'a, str>>,
@@ -205,18 +1080,503 @@ impl<'a> EntityBuilder<'a> {
         self
     }
 
-    pub fn with_object(mut self, object: Object) -> Self {
-        self.object = Some(object);
+    /// Attaches a component of any type to the entity once it's added.
+    pub fn with<T: Component>(mut self, component: T) -> Self {
+        self.components.push(Box::new(move |components, entity| {
+            components.insert(entity, component);
+        }));
         self
     }
 
-    pub fn with_movement(mut self, movement: Movement) -> Self {
-        self.movement = Some(movement);
-        self
+    pub fn with_object(self, object: Object) -> Self {
+        self.with(object)
     }
 
-    pub fn with_input(mut self, input: Input) -> Self {
-        self.input = Some(input);
+    pub fn with_movement(self, movement: Movement) -> Self {
+        self.with(movement)
+    }
+
+    pub fn with_input(self, input: Input) -> Self {
+        self.with(input)
+    }
+
+    pub fn with_platform(self, platform: Platform) -> Self {
+        self.with(platform)
+    }
+
+    pub fn with_trigger(self, trigger: Trigger) -> Self {
+        self.with(trigger)
+    }
+
+    /// Attaches the entity to a parent once it's added, as `EntityManager::set_parent` does,
+    /// so it's left without one if the parent has been despawned by then.
+    pub fn with_parent(mut self, parent: Entity, offset: Vector2<f32>) -> Self {
+        self.parent = Some((parent, offset));
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{Action, Actions};
+    use crate::input_source::Scripted;
+    use crate::testing::{self, run, run_with};
+    use crate::world::World;
+    use nalgebra::{Point2, Vector2};
+
+    #[test]
+    fn stale_handles_are_rejected_after_despawning() {
+        let mut entity_manager = EntityManager::new();
+        let entity =
+            entity_manager.add(EntityBuilder::new().with_object(Object::with_size(10.0, 10.0)));
+        assert!(entity_manager.despawn(entity));
+
+        assert!(!entity_manager.is_alive(entity));
+        assert!(entity_manager.get_object(entity).is_none());
+        assert!(!entity_manager.insert(entity, Movement::new()));
+        assert!(!entity_manager.despawn(entity));
+    }
+
+    #[test]
+    fn reused_indices_get_a_new_generation() {
+        let mut allocator = Allocator::new();
+        let first = allocator.allocate();
+        assert!(allocator.deallocate(first));
+
+        let second = allocator.allocate();
+        assert_eq!(second.index, first.index);
+        assert_eq!(second.generation, first.generation + 1);
+        assert_ne!(second, first);
+        assert!(!allocator.is_alive(first));
+        assert!(allocator.is_alive(second));
+        assert!(!allocator.deallocate(first));
+    }
+
+    #[test]
+    fn components_of_any_type_can_be_attached_changed_and_removed() {
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+
+        let mut entity_manager = EntityManager::new();
+        let entity = entity_manager.add(EntityBuilder::new());
+        let other = entity_manager.add(EntityBuilder::new());
+        assert!(entity_manager.insert(entity, Health(3)));
+        assert_eq!(entity_manager.get::<Health>(entity), Some(&Health(3)));
+        assert_eq!(entity_manager.get::<Health>(other), None);
+
+        entity_manager.get_mut::<Health>(entity).unwrap().0 -= 1;
+        assert_eq!(entity_manager.get::<Health>(entity), Some(&Health(2)));
+
+        assert_eq!(entity_manager.remove::<Health>(entity), Some(Health(2)));
+        assert_eq!(entity_manager.get::<Health>(entity), None);
+        assert_eq!(entity_manager.remove::<Health>(entity), None);
+
+        entity_manager.despawn(other);
+        assert!(!entity_manager.insert(other, Health(1)));
+        assert_eq!(entity_manager.get::<Health>(other), None);
+    }
+
+    #[test]
+    fn queued_despawns_wait_for_the_flush() {
+        let mut entity_manager = EntityManager::new();
+        let entity = entity_manager.add(EntityBuilder::new());
+        entity_manager.queue_despawn(entity);
+        entity_manager.queue_despawn(entity);
+        assert!(entity_manager.is_alive(entity));
+
+        entity_manager.flush_despawns();
+        assert!(!entity_manager.is_alive(entity));
+
+        entity_manager.flush_despawns();
+        let events: Vec<EntityEvent> = entity_manager.drain_entity_events().collect();
+        assert_eq!(
+            events,
+            vec![EntityEvent::Spawned(entity), EntityEvent::Despawned(entity)]
+        );
+    }
+
+    #[test]
+    fn spawns_and_despawns_are_reported_once() {
+        let mut entity_manager = EntityManager::new();
+        let parent = entity_manager.add(EntityBuilder::new());
+        let child =
+            entity_manager.add(EntityBuilder::new().with_parent(parent, Vector2::new(0.0, 0.0)));
+        let events: Vec<EntityEvent> = entity_manager.drain_entity_events().collect();
+        assert_eq!(
+            events,
+            vec![EntityEvent::Spawned(parent), EntityEvent::Spawned(child)]
+        );
+
+        entity_manager.despawn(parent);
+        entity_manager.despawn(parent);
+        let events: Vec<EntityEvent> = entity_manager.drain_entity_events().collect();
+        assert_eq!(
+            events,
+            vec![
+                EntityEvent::Despawned(child),
+                EntityEvent::Despawned(parent)
+            ]
+        );
+        assert_eq!(entity_manager.drain_entity_events().count(), 0);
+    }
+
+    #[test]
+    fn entities_are_looked_up_by_tag() {
+        let mut entity_manager = EntityManager::new();
+        let first = entity_manager.add(EntityBuilder::new().with_tag("enemy").with_tag("flying"));
+        let second = entity_manager.add(EntityBuilder::new());
+        let third = entity_manager.add(EntityBuilder::new().with_tag("enemy"));
+        assert!(entity_manager.add_tag(second, "enemy"));
+
+        let enemies: Vec<Entity> = entity_manager.tagged("enemy").collect();
+        assert_eq!(enemies, vec![first, second, third]);
+        assert!(entity_manager.has_tag(first, "flying"));
+        assert!(!entity_manager.has_tag(second, "flying"));
+        assert_eq!(entity_manager.tagged("missing").count(), 0);
+
+        assert!(entity_manager.remove_tag(second, "enemy"));
+        assert!(!entity_manager.remove_tag(second, "enemy"));
+        entity_manager.despawn(third);
+        assert!(!entity_manager.add_tag(third, "enemy"));
+        let enemies: Vec<Entity> = entity_manager.tagged("enemy").collect();
+        assert_eq!(enemies, vec![first]);
+    }
+
+    #[test]
+    fn tagged_queries_only_match_entities_with_the_tag() {
+        let mut entity_manager = EntityManager::new();
+        let moving = |tag| {
+            EntityBuilder::new()
+                .with_tag(tag)
+                .with_object(Object::with_size(10.0, 10.0))
+                .with_movement(Movement::new())
+        };
+        let enemy = entity_manager.add(moving("enemy"));
+        entity_manager.add(moving("player"));
+        entity_manager.add(
+            EntityBuilder::new()
+                .with_tag("enemy")
+                .with_object(Object::with_size(10.0, 10.0)),
+        );
+
+        let matched: Vec<Entity> = entity_manager
+            .query_tagged::<(&Object, &mut Movement)>("enemy")
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(matched, vec![enemy]);
+    }
+
+    #[test]
+    fn prefabs_tag_the_entities_made_from_them() {
+        let mut world = testing::empty_world(Map::default());
+        let walker = world.spawn("walker", 700.0, 920.0).unwrap();
+        let coin = world.spawn("coin", 800.0, 910.0).unwrap();
+
+        assert!(world.entity_manager.has_tag(walker, "enemy"));
+        assert!(!world.entity_manager.has_tag(walker, "collectible"));
+        let collectibles: Vec<Entity> = world.entity_manager.tagged("collectible").collect();
+        assert_eq!(collectibles, vec![coin]);
+    }
+
+    #[test]
+    fn built_entities_are_only_attached_to_living_parents() {
+        let mut entity_manager = EntityManager::new();
+        let parent = entity_manager.add(EntityBuilder::new());
+        let child =
+            entity_manager.add(EntityBuilder::new().with_parent(parent, Vector2::new(0.0, -10.0)));
+        assert_eq!(entity_manager.get_parent(child), Some(parent));
+
+        entity_manager.despawn(parent);
+        let orphan =
+            entity_manager.add(EntityBuilder::new().with_parent(parent, Vector2::new(0.0, -10.0)));
+        assert_eq!(entity_manager.get_parent(orphan), None);
+    }
+
+    fn object_at(x: f32, y: f32) -> EntityBuilder<'static> {
+        EntityBuilder::new().with_object(Object::with_size(10.0, 10.0).at(x, y))
+    }
+
+    fn point(entity_manager: &EntityManager, entity: Entity) -> (f32, f32) {
+        let rect = entity_manager.get_object(entity).unwrap().rect;
+        (rect.x, rect.y)
+    }
+
+    #[test]
+    fn children_follow_their_parents_at_their_offset() {
+        let mut entity_manager = EntityManager::new();
+        let parent = entity_manager.add(object_at(100.0, 100.0));
+        let child =
+            entity_manager.add(object_at(0.0, 0.0).with_parent(parent, Vector2::new(10.0, -20.0)));
+        let grandchild =
+            entity_manager.add(object_at(0.0, 0.0).with_parent(child, Vector2::new(5.0, 5.0)));
+
+        entity_manager.update_hierarchy();
+        assert_eq!(point(&entity_manager, child), (110.0, 80.0));
+        assert_eq!(point(&entity_manager, grandchild), (115.0, 85.0));
+
+        entity_manager
+            .get_object_mut(parent)
+            .unwrap()
+            .move_by(90.0, 200.0);
+        entity_manager.update_hierarchy();
+        assert_eq!(point(&entity_manager, child), (200.0, 280.0));
+        assert_eq!(point(&entity_manager, grandchild), (205.0, 285.0));
+    }
+
+    #[test]
+    fn despawning_a_parent_despawns_its_descendants() {
+        let mut entity_manager = EntityManager::new();
+        let parent = entity_manager.add(object_at(0.0, 0.0));
+        let child =
+            entity_manager.add(object_at(0.0, 0.0).with_parent(parent, Vector2::new(0.0, 0.0)));
+        let grandchild =
+            entity_manager.add(object_at(0.0, 0.0).with_parent(child, Vector2::new(0.0, 0.0)));
+        let other = entity_manager.add(object_at(0.0, 0.0));
+
+        assert!(entity_manager.despawn(parent));
+        assert!(!entity_manager.is_alive(child));
+        assert!(!entity_manager.is_alive(grandchild));
+        assert!(entity_manager.get_object(grandchild).is_none());
+        assert!(entity_manager.is_alive(other));
+    }
+
+    #[test]
+    fn parents_cannot_be_descendants_or_despawned() {
+        let mut entity_manager = EntityManager::new();
+        let offset = Vector2::new(0.0, 0.0);
+        let parent = entity_manager.add(object_at(0.0, 0.0));
+        let child = entity_manager.add(object_at(0.0, 0.0).with_parent(parent, offset));
+        let grandchild = entity_manager.add(object_at(0.0, 0.0).with_parent(child, offset));
+
+        assert!(!entity_manager.set_parent(parent, parent, offset));
+        assert!(!entity_manager.set_parent(parent, child, offset));
+        assert!(!entity_manager.set_parent(parent, grandchild, offset));
+        assert_eq!(entity_manager.get_parent(parent), None);
+
+        let despawned = entity_manager.add(object_at(0.0, 0.0));
+        entity_manager.despawn(despawned);
+        assert!(!entity_manager.set_parent(grandchild, despawned, offset));
+        assert!(!entity_manager.set_parent(despawned, parent, offset));
+        assert_eq!(entity_manager.get_parent(grandchild), Some(child));
+    }
+
+    /// A room with a one-way platform, whose top is at 420, a jump's height above the floor.
+    const ONE_WAY_ROOM: &str = "
+        ##########
+        #........#
+        #........#
+        #........#
+        #........#
+        #........#
+        #========#
+        ##########
+    ";
+
+    fn player(world: &World) -> Object {
+        let entity_manager = &world.entity_manager;
+        entity_manager
+            .by_name("player")
+            .and_then(|player| entity_manager.get_object(player))
+            .cloned()
+            .unwrap()
+    }
+
+    /// A room with a hill up to a plateau, whose top is at 350, 70 above the floor.
+    const HILL_ROOM: &str = "
+        ##########
+        #........#
+        #........#
+        #........#
+        #........#
+        #..../####
+        ##########
+    ";
+
+    /// The same room, reaching the plateau up a gentle hill instead.
+    const GENTLE_HILL_ROOM: &str = "
+        ##########
+        #........#
+        #........#
+        #........#
+        #........#
+        #...lL####
+        ##########
+    ";
+
+    fn world_with_player_at(room: &str, x: f32, y: f32) -> World<'static> {
+        let mut world = testing::world(Map::from_ascii(room));
+        let player = world.prefabs.builder("player", x, y).unwrap();
+        world.entity_manager.add(player.with_name("player"));
+        world
+    }
+
+    fn holding(action: Action, ticks: u32) -> Scripted {
+        Scripted::new().hold(Actions::new().with(action), ticks)
+    }
+
+    #[test]
+    fn one_way_platforms_catch_entities_falling_onto_them() {
+        let mut world = world_with_player_at(ONE_WAY_ROOM, 140.0, 100.0);
+        run(&mut world, 60);
+        assert_eq!(player(&world).rect.y, 420.0 - 106.0);
+    }
+
+    #[test]
+    fn one_way_platforms_can_be_jumped_up_through() {
+        let mut world = world_with_player_at(ONE_WAY_ROOM, 140.0, 490.0 - 106.0);
+        run(&mut world, 10);
+        assert_eq!(player(&world).rect.y, 490.0 - 106.0);
+
+        run_with(&mut world, &mut holding(Action::Jump, 1), 120);
+        assert_eq!(player(&world).rect.y, 420.0 - 106.0);
+    }
+
+    #[test]
+    fn crouching_drops_through_one_way_platforms() {
+        let mut world = world_with_player_at(ONE_WAY_ROOM, 140.0, 100.0);
+        run(&mut world, 60);
+
+        run_with(&mut world, &mut holding(Action::Crouch, 60), 60);
+        assert_eq!(player(&world).rect.y, 490.0 - 106.0);
+    }
+
+    #[test]
+    fn riders_move_as_far_as_their_platform_actually_went() {
+        let map = Map::from_ascii(
+            "
+            ##########
+            #........#
+            #........#
+            #........#
+            ##########
+        ",
+        );
+        let mut entity_manager = EntityManager::new();
+        let platform = entity_manager.add(
+            EntityBuilder::new()
+                .with_object(Object::with_size(140.0, 35.0).at(400.0, 200.0))
+                .with_platform(
+                    Platform::new(vec![Point2::new(400.0, 200.0), Point2::new(600.0, 200.0)])
+                        .with_speed(10.0),
+                ),
+        );
+        // Pushed along until the wall at 630 stops it, and the platform with it.
+        entity_manager.add(
+            EntityBuilder::new()
+                .with_object(Object::with_size(60.0, 60.0).at(545.0, 190.0))
+                .with_movement(Movement::new()),
+        );
+        let rider = entity_manager.add(
+            EntityBuilder::new()
+                .with_object(Object::with_size(48.0, 106.0).at(420.0, 200.0 - 106.0))
+                .with_movement(Movement::new()),
+        );
+
+        for _ in 0..5 {
+            entity_manager.update_platforms(&map);
+        }
+
+        let platform = entity_manager.get_object(platform).unwrap().rect;
+        assert_eq!(platform.x, 630.0 - 60.0 - 140.0);
+        assert_eq!(
+            entity_manager.get_object(rider).unwrap().rect.x,
+            420.0 + 30.0
+        );
+    }
+
+    fn falling_box(x: f32, y: f32) -> EntityBuilder<'static> {
+        EntityBuilder::new()
+            .with_object(Object::with_size(60.0, 60.0).at(x, y))
+            .with_movement(
+                Movement::new()
+                    .with_max_speed((Some(10.0), Some(20.0)))
+                    .with_force(Vector2::new(0.0, 0.5)),
+            )
+    }
+
+    #[test]
+    fn entities_land_on_top_of_each_other() {
+        let mut world = testing::world(Map::default());
+        let bottom = world.entity_manager.add(falling_box(700.0, 920.0));
+        let top = world.entity_manager.add(falling_box(710.0, 700.0));
+        run(&mut world, 120);
+
+        assert_eq!(
+            world.entity_manager.get_object(bottom).unwrap().rect.y,
+            920.0
+        );
+        assert_eq!(world.entity_manager.get_object(top).unwrap().rect.y, 860.0);
+
+        let movement = world.entity_manager.get::<Movement>(top).unwrap();
+        assert!(movement.is_grounded());
+        assert_eq!(movement.get_speed().y, 0.0);
+    }
+
+    #[test]
+    fn enemies_pass_through_each_other() {
+        let mut world = testing::world(Map::default());
+        let first = world.spawn("chaser", 700.0, 920.0).unwrap();
+        let second = world.spawn("chaser", 720.0, 920.0).unwrap();
+        let third = world.spawn("chaser", 1000.0, 920.0).unwrap();
+        let other = world.entity_manager.add(falling_box(1040.0, 920.0));
+        run(&mut world, 30);
+
+        let x = |entity| world.entity_manager.get_object(entity).unwrap().rect.x;
+        assert_eq!(x(first), 700.0);
+        assert_eq!(x(second), 720.0);
+        assert_eq!(x(third), 990.0);
+        assert_eq!(x(other), 1050.0);
+    }
+
+    /// Walks the player in the direction for a number of ticks, checking that it stays on the
+    /// ground the whole way.
+    fn walk(world: &mut World<'static>, action: Action, ticks: u32) {
+        let mut input = holding(action, ticks);
+        for _ in 0..ticks {
+            run_with(world, &mut input, 1);
+            let player = world.entity_manager.by_name("player").unwrap();
+            assert!(world
+                .entity_manager
+                .get::<Movement>(player)
+                .unwrap()
+                .is_grounded());
+        }
+    }
+
+    #[test]
+    fn entities_walk_up_slopes() {
+        let mut world = world_with_player_at(HILL_ROOM, 140.0, 420.0 - 106.0);
+        run(&mut world, 10);
+
+        walk(&mut world, Action::MoveRight, 90);
+        assert_eq!(
+            player(&world).rect,
+            Rect::new(630.0 - 48.0, 350.0 - 106.0, 48.0, 106.0)
+        );
+    }
+
+    #[test]
+    fn entities_walk_down_slopes_without_leaving_the_ground() {
+        let mut world = world_with_player_at(HILL_ROOM, 560.0, 350.0 - 106.0);
+        run(&mut world, 10);
+
+        walk(&mut world, Action::MoveLeft, 90);
+        assert_eq!(
+            player(&world).rect,
+            Rect::new(70.0, 420.0 - 106.0, 48.0, 106.0)
+        );
+    }
+
+    #[test]
+    fn entities_walk_up_and_down_gentle_slopes() {
+        let mut world = world_with_player_at(GENTLE_HILL_ROOM, 140.0, 420.0 - 106.0);
+        run(&mut world, 10);
+
+        walk(&mut world, Action::MoveRight, 90);
+        assert_eq!(player(&world).rect.y, 350.0 - 106.0);
+
+        walk(&mut world, Action::MoveLeft, 90);
+        assert_eq!(player(&world).rect.y, 420.0 - 106.0);
+    }
+}
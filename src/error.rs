@@ -5,6 +5,10 @@ pub enum Error {
     IOFailure(std::io::Error),
     CoffeeError(coffee::Error),
     SerdeError(serde_json::Error),
+    UnknownLayer(String),
+    UnknownPrefab(String),
+    UnsupportedSaveVersion(u64),
+    UnknownKey(String),
 }
 
 impl std::error::Error for Error {}
@@ -0,0 +1,190 @@
+use crate::entity::Entity;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::mem;
+
+/// Any type which can be published on the `Events` bus.
+pub trait Event: Any + Debug {}
+
+impl<T: Any + Debug> Event for T {}
+
+/// Published each tick that an entity stands on a surface which deals damage.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Damaged {
+    pub entity: Entity,
+    pub amount: f32,
+}
+
+/// The queue for one type of event, with its type erased so that every queue can be advanced
+/// at once.
+trait Queue: Any {
+    fn advance(&mut self);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct EventQueue<E> {
+    published: Vec<E>,
+    readable: Vec<E>,
+}
+
+impl<E: Event> Queue for EventQueue<E> {
+    fn advance(&mut self) {
+        self.readable = mem::replace(&mut self.published, Vec::new());
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A bus for systems to tell each other what happened during a tick, such as an entity
+/// entering a trigger.
+///
+/// Events published during a tick only become readable once the tick is over, and then stay
+/// readable for the whole of the next tick. That way every system sees every event exactly
+/// once, whichever order the systems run in.
+pub struct Events {
+    queues: HashMap<TypeId, Box<dyn Queue>>,
+    tick: u64,
+    logging: bool,
+    published_log: Vec<String>,
+    log: Vec<String>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self {
+            queues: HashMap::new(),
+            tick: 0,
+            logging: false,
+            published_log: Vec::new(),
+            log: Vec::new(),
+        }
+    }
+
+    pub fn publish<E: Event>(&mut self, event: E) {
+        if self.logging {
+            self.published_log.push(format!("{:?}", event));
+        }
+        self.queue_mut::<E>().published.push(event);
+    }
+
+    /// Starts or stops keeping the `log`. It's off to begin with, since every event has to be
+    /// formatted to keep it.
+    pub fn set_logging(&mut self, logging: bool) {
+        self.logging = logging;
+    }
+
+    /// The events of the given type which were published during the previous tick, in the
+    /// order they were published.
+    pub fn read<E: Event>(&self) -> impl Iterator<Item = &E> {
+        self.queues
+            .get(&TypeId::of::<E>())
+            .and_then(|queue| queue.as_any().downcast_ref::<EventQueue<E>>())
+            .into_iter()
+            .flat_map(|queue| queue.readable.iter())
+    }
+
+    /// Ends the tick, making the events published during it readable in place of the ones
+    /// from the tick before.
+    pub fn advance(&mut self) {
+        for queue in self.queues.values_mut() {
+            queue.advance();
+        }
+        self.log = mem::replace(&mut self.published_log, Vec::new());
+        self.tick += 1;
+    }
+
+    /// The number of ticks which have ended.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Every event published during the previous tick, of any type, as it would be printed
+    /// with `{:?}`. Empty unless logging is on.
+    pub fn log(&self) -> &[String] {
+        &self.log
+    }
+
+    fn queue_mut<E: Event>(&mut self) -> &mut EventQueue<E> {
+        self.queues
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| {
+                Box::new(EventQueue::<E> {
+                    published: Vec::new(),
+                    readable: Vec::new(),
+                })
+            })
+            .as_any_mut()
+            .downcast_mut()
+            .expect("event queue holds the wrong type")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Ping(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Pong;
+
+    #[test]
+    fn events_are_read_once_the_tick_they_were_published_in_ends() {
+        let mut events = Events::new();
+        events.publish(Ping(1));
+        events.publish(Ping(2));
+        events.publish(Pong);
+        assert_eq!(events.read::<Ping>().count(), 0);
+
+        events.advance();
+        assert_eq!(
+            events.read::<Ping>().collect::<Vec<_>>(),
+            vec![&Ping(1), &Ping(2)]
+        );
+        assert_eq!(events.read::<Pong>().collect::<Vec<_>>(), vec![&Pong]);
+    }
+
+    #[test]
+    fn events_are_cleared_after_one_tick() {
+        let mut events = Events::new();
+        events.publish(Ping(1));
+        events.advance();
+        events.publish(Ping(2));
+        events.advance();
+        assert_eq!(events.read::<Ping>().collect::<Vec<_>>(), vec![&Ping(2)]);
+
+        events.advance();
+        assert_eq!(events.read::<Ping>().count(), 0);
+        assert_eq!(events.tick(), 3);
+    }
+
+    #[test]
+    fn only_events_published_while_logging_are_logged() {
+        let mut events = Events::new();
+        events.publish(Ping(1));
+        events.advance();
+        assert!(events.log().is_empty());
+
+        events.set_logging(true);
+        events.publish(Ping(2));
+        events.publish(Pong);
+        events.advance();
+        assert_eq!(events.log(), ["Ping(2)", "Pong"]);
+
+        events.set_logging(false);
+        events.publish(Ping(3));
+        events.advance();
+        assert!(events.log().is_empty());
+    }
+}
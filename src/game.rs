@@ -4,29 +4,36 @@ use coffee::load::loading_screen::ProgressBar;
 use coffee::load::Join;
 use coffee::load::Task;
 use coffee::Debug;
-use nalgebra::Vector2;
 use std::borrow::Cow;
-use std::collections::HashMap;
-use uuid::Uuid;
+use std::fs::File;
+use std::io;
+use std::time::Instant;
 
 use crate::assets::Assets;
-use crate::camera::Camera;
+use crate::bindings::{Action, Bindings};
+use crate::camera::{Camera, Follow};
 use crate::config::Config;
-use crate::entity::{EntityBuilder, EntityManager};
-use crate::input::{Input, PlayerInput};
-use crate::map::Map;
-use crate::object::{Movement, Object};
+use crate::error::Error;
+use crate::input_source::{InputSource, Keyboard};
+use crate::object::Object;
+use crate::reachability::unreachable_targets;
 use crate::rect::Rect;
+use crate::save::SAVE_PATH;
+use crate::system::Scheduler;
+use crate::world::World;
 use coffee::Game;
 
 pub struct Platformrs<'a> {
     assets: Assets<'a>,
-    map: Map<'a>,
+    world: World<'a>,
+    scheduler: Scheduler<'a>,
     config: Config,
+    keyboard: Keyboard,
+    paused: bool,
     camera: Camera,
+    last_frame: Instant,
     batch: Batch,
     debug_sheet: Image,
-    entity_manager: EntityManager<'a>,
 }
 
 impl<'a> Game for Platformrs<'a> {
@@ -39,7 +46,7 @@ impl<'a> Game for Platformrs<'a> {
                 "Loading assets...",
                 Task::using_gpu(|_gpu| Assets::load().map_err(|e| coffee::Error::from(e))),
             ),
-            Task::stage("Loading map data...", Task::new(|| Map::load())),
+            Task::stage("Loading map data...", Task::new(|| World::load())),
             Task::stage(
                 "Loading spritesheet",
                 Task::using_gpu(|mut gpu| Image::new(&mut gpu, "assets/tiles.png")),
@@ -50,72 +57,103 @@ impl<'a> Game for Platformrs<'a> {
             ),
         )
             .join()
-            .map(|(assets, map, spritesheet, debug_sheet)| {
+            .map(|(assets, mut world, spritesheet, debug_sheet)| {
                 let config = Config::new();
-                let camera = Camera::new(
-                    Rect::default().size(config.screen_width as f32, config.screen_height as f32),
-                )
-                .with_bounds(Rect::default().size(
-                    (map.width * config.tilesize) as f32,
-                    (map.height * config.tilesize) as f32,
-                ));
-
-                let mut entity_manager = EntityManager::new();
-
-                entity_manager.add(
-                    EntityBuilder::new()
-                        .with_name("player")
-                        .with_asset("hillSmall")
-                        .with_object(Object::with_size(48.0, 106.0).at(100.0, 100.0))
-                        .with_movement(
-                            Movement::new()
-                                .with_max_speed((Some(10.0), Some(20.0)))
-                                .with_force(Vector2::new(0.0, 0.5)),
-                        )
-                        .with_input(Input::Player(PlayerInput::new())),
-                );
+                let keyboard = Keyboard::new(Bindings::load().unwrap());
+                let (screen_width, screen_height) =
+                    (config.screen_width as f32, config.screen_height as f32);
+                let camera = Camera::new(Rect::default().size(screen_width, screen_height))
+                    .with_bounds(Rect::default().size(
+                        (world.map.width * config.tilesize) as f32,
+                        (world.map.height * config.tilesize) as f32,
+                    ))
+                    .with_follow(Follow::Damped {
+                        stiffness_x: 6.0,
+                        stiffness_y: 3.0,
+                    })
+                    .with_dead_zone(Rect::new(
+                        screen_width / 2.0 - 100.0,
+                        screen_height / 2.0 - 150.0,
+                        200.0,
+                        300.0,
+                    ))
+                    .with_look_ahead(150.0, 0.0);
+
+                world.spawn_map_entities().unwrap();
+
+                let scheduler = Scheduler::default();
+                if cfg!(feature = "debug") {
+                    world.events.set_logging(true);
+
+                    for spawn in
+                        unreachable_targets(&world.map, &world.prefabs, &world.acceleration)
+                            .unwrap()
+                    {
+                        println!(
+                            "The {} at ({}, {}) can't be reached by the player.",
+                            spawn.prefab, spawn.x, spawn.y
+                        );
+                    }
+                }
 
                 Self {
                     assets,
-                    map,
+                    world,
+                    scheduler,
                     config,
+                    keyboard,
+                    paused: false,
                     camera,
+                    last_frame: Instant::now(),
                     debug_sheet,
                     batch: Batch::new(spritesheet),
-                    entity_manager,
                 }
             })
     }
 
     fn interact(&mut self, input: &mut KeyboardAndMouse, _window: &mut Window) {
-        for (uuid, entity_input) in self.entity_manager.get_inputs_mut() {
-            entity_input.update(input);
+        let previous = self.keyboard.held().clone();
+        self.keyboard.capture(input);
+
+        let held = self.keyboard.held();
+        let pause = held.just_pressed(&previous, Action::Pause);
+        let quick_save = held.just_pressed(&previous, Action::QuickSave);
+        let quick_load = held.just_pressed(&previous, Action::QuickLoad);
+
+        if pause {
+            self.paused = !self.paused;
+        }
+        if quick_save {
+            File::create(SAVE_PATH)
+                .map_err(Error::from)
+                .and_then(|file| self.world.save(io::BufWriter::new(file)))
+                .unwrap_or_else(|e| println!("Unable to write the save file: {}.", e));
+        }
+        if quick_load {
+            File::open(SAVE_PATH)
+                .map_err(Error::from)
+                .and_then(|file| self.world.restore(io::BufReader::new(file)))
+                .unwrap_or_else(|e| println!("Unable to read the save file: {}.", e));
         }
     }
 
     fn update(&mut self, _window: &Window) {
-        let object_forces = self
-            .entity_manager
-            .get_inputs()
-            .iter()
-            .map(|(uuid, input)| (**uuid, input.get_force()))
-            .collect::<HashMap<Uuid, Vector2<f32>>>();
-
-        for (uuid, movement) in self.entity_manager.get_movements_mut() {
-            if let Some(force) = object_forces.get(&uuid) {
-                movement.add_instantaneous_force(*force);
-            }
-        }
+        if !self.paused {
+            let actions = self.keyboard.next();
+            self.world.update_inputs(&actions);
+            self.scheduler.run(&mut self.world);
 
-        for uuid in self.entity_manager.get_entities() {
-            self.entity_manager.update(uuid, &self.map);
+            let events = &self.world.events;
+            for event in events.log() {
+                println!("[tick {}] {}", events.tick(), event);
+            }
         }
     }
 
     fn draw(&mut self, frame: &mut Frame, _timer: &coffee::Timer) {
         frame.clear(Color::BLACK);
 
-        for (x, y, cell) in self.map.iter() {
+        for (x, y, cell) in self.world.map.iter() {
             let source = *self
                 .assets
                 .offsets
@@ -131,13 +169,13 @@ impl<'a> Game for Platformrs<'a> {
             });
         }
 
-        for (uuid, asset) in self.entity_manager.get_assets() {
-            if let Some(object) = self.entity_manager.get_object(uuid) {
+        for (entity, asset) in self.world.entity_manager.get_assets() {
+            if let Some(object) = self.world.entity_manager.get_object(*entity) {
                 if !object.visible {
                     continue;
                 }
 
-                if let Some(offset) = self.assets.offsets.get(&asset) {
+                if let Some(offset) = self.assets.offsets.get(asset) {
                     self.batch.add(Sprite {
                         source: *offset,
                         position: object.rect.point(),
@@ -148,15 +186,21 @@ impl<'a> Game for Platformrs<'a> {
         }
 
         let default = Object::with_size(70.0, 70.0);
-        let object = self
-            .entity_manager
-            .get_object(self.entity_manager.by_name("player"))
+        let entity_manager = &self.world.entity_manager;
+        let object = entity_manager
+            .by_name("player")
+            .and_then(|player| entity_manager.get_object(player))
             .unwrap_or(&default);
 
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame);
+        self.last_frame = now;
+        let elapsed = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1_000_000_000.0;
+
         self.batch.draw(
             &mut frame
                 .as_target()
-                .transform(self.camera.update(Some(&object.rect))),
+                .transform(self.camera.update(Some(&object.rect), elapsed)),
         );
         self.batch.clear();
     }
@@ -165,12 +209,13 @@ impl<'a> Game for Platformrs<'a> {
         let mut batch = Batch::new(self.debug_sheet.clone());
 
         let default = Object::with_size(70.0, 70.0);
-        let player = self
-            .entity_manager
-            .get_object(self.entity_manager.by_name("player"))
+        let entity_manager = &self.world.entity_manager;
+        let player = entity_manager
+            .by_name("player")
+            .and_then(|player| entity_manager.get_object(player))
             .unwrap_or(&default);
 
-        for cell in self.map.collidable_tiles(&player.rect) {
+        for cell in self.world.map.collidable_tiles(&player.rect) {
             batch.add(Sprite {
                 source: Rectangle {
                     x: 0,
@@ -197,6 +242,7 @@ impl<'a> Game for Platformrs<'a> {
         }
 
         for cell in self
+            .world
             .map
             .collidable_tiles(&Rect::from_point(input.cursor_position()))
         {
@@ -211,17 +257,13 @@ impl<'a> Game for Platformrs<'a> {
                 scale: (1.0, 1.0),
             });
         }
-        batch.draw(
-            &mut frame
-                .as_target()
-                .transform(self.camera.get_transform(Some(&player.rect))),
-        );
+        batch.draw(&mut frame.as_target().transform(self.camera.get_transform()));
 
         debug.draw(frame);
     }
 
     fn on_close_request(&mut self) -> bool {
-        self.map.write();
+        self.world.map.write();
         return true;
     }
 }
@@ -0,0 +1,21 @@
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::entity::Entity;
+
+/// Attaches an entity to another, so that its `Object` follows the parent's around.
+///
+/// The child's rect is placed at `offset` from the top left corner of the parent's rect each
+/// tick, and the child is despawned along with its parent. Children aren't pushed apart from
+/// other entities, since wherever they're pushed to they'd be put straight back.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Parent {
+    pub entity: Entity,
+    pub offset: Vector2<f32>,
+}
+
+impl Parent {
+    pub fn new(entity: Entity, offset: Vector2<f32>) -> Self {
+        Self { entity, offset }
+    }
+}
@@ -1,16 +1,25 @@
-use coffee::input::{keyboard, KeyboardAndMouse};
+use crate::ai::{Chase, Jumper, Patrol, Surroundings};
+use crate::bindings::{Action, Actions};
 use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
 
+/// The upwards force applied to an entity for each tick it's jumping.
+pub const JUMP_FORCE: f32 = -15.0;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub enum MoveDirection {
     Left,
     Right,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PlayerInput {
     move_direction: Option<MoveDirection>,
     jump: bool,
     jumping: bool,
     crouched: bool,
+    #[serde(default)]
+    running: bool,
 }
 
 impl PlayerInput {
@@ -20,65 +29,114 @@ impl PlayerInput {
             jump: false,
             jumping: false,
             crouched: false,
+            running: false,
         }
     }
 
-    fn update(&mut self, input: &mut KeyboardAndMouse) {
+    fn update(&mut self, actions: &Actions) {
         let mut move_direction = None;
-        if input.is_key_pressed(keyboard::KeyCode::A) {
+        if actions.is_active(Action::MoveLeft) {
             move_direction = Some(MoveDirection::Left);
         }
-        if input.is_key_pressed(keyboard::KeyCode::D) {
+        if actions.is_active(Action::MoveRight) {
             move_direction = Some(MoveDirection::Right);
         }
 
-        let jump = input.is_key_pressed(keyboard::KeyCode::W);
-        let crouched = input.is_key_pressed(keyboard::KeyCode::S);
+        let jump = actions.is_active(Action::Jump);
+        let crouched = actions.is_active(Action::Crouch);
+        let running = actions.is_active(Action::Run);
 
         if !self.jumping {
             // self.jumping = true;
             self.jump = jump;
         }
         self.crouched = crouched;
+        self.running = running;
         self.move_direction = move_direction;
     }
 
-    fn get_force(&self) -> Vector2<f32> {
-        let mut result = Vector2::new(0.0, 0.0);
-
+    fn get_direction(&self) -> f32 {
         match self.move_direction {
-            Some(MoveDirection::Left) => {
-                result.x = -5.0;
-            }
-            Some(MoveDirection::Right) => {
-                result.x = 5.0;
-            }
-            _ => {}
+            Some(MoveDirection::Left) => -1.0,
+            Some(MoveDirection::Right) => 1.0,
+            None => 0.0,
         }
+    }
 
+    fn get_force(&self) -> Vector2<f32> {
+        let mut result = Vector2::new(0.0, 0.0);
         if self.jump {
-            result.y = -15.0;
+            result.y = JUMP_FORCE;
         }
         result
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Input {
     Player(PlayerInput),
+    Patrol(Patrol),
+    Chase(Chase),
+    Jumper(Jumper),
     None,
 }
 
 impl Input {
-    pub fn update(&mut self, input: &mut KeyboardAndMouse) {
+    pub fn update(&mut self, actions: &Actions) {
         match self {
-            Input::Player(player_input) => player_input.update(input),
-            Input::None => return,
+            Input::Player(player_input) => player_input.update(actions),
+            _ => return,
+        }
+    }
+
+    /// Lets AI controlled inputs decide what to do this tick.
+    pub fn think(&mut self, surroundings: &Surroundings) {
+        match self {
+            Input::Patrol(patrol) => patrol.think(surroundings),
+            Input::Chase(chase) => chase.think(surroundings),
+            Input::Jumper(jumper) => jumper.think(surroundings),
+            _ => return,
+        }
+    }
+
+    pub fn is_ai(&self) -> bool {
+        match self {
+            Input::Patrol(_) | Input::Chase(_) | Input::Jumper(_) => true,
+            _ => false,
         }
     }
     pub fn get_force(&self) -> Vector2<f32> {
         match self {
             Input::Player(player_input) => player_input.get_force(),
-            Input::None => Vector2::new(0.0, 0.0),
+            Input::Chase(chase) => chase.get_force(),
+            Input::Jumper(jumper) => jumper.get_force(),
+            Input::Patrol(_) | Input::None => Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// The horizontal direction being steered in, from -1.0 for left to 1.0 for right, or
+    /// `None` if the entity isn't steered at all.
+    pub fn get_direction(&self) -> Option<f32> {
+        match self {
+            Input::Player(player_input) => Some(player_input.get_direction()),
+            Input::Patrol(patrol) => Some(patrol.get_direction()),
+            Input::Chase(chase) => Some(chase.get_direction()),
+            Input::Jumper(_) => Some(0.0),
+            Input::None => None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        match self {
+            Input::Player(player_input) => player_input.running,
+            _ => false,
+        }
+    }
+
+    pub fn is_crouched(&self) -> bool {
+        match self {
+            Input::Player(player_input) => player_input.crouched,
+            _ => false,
         }
     }
 }
@@ -0,0 +1,236 @@
+use coffee::input::KeyboardAndMouse;
+#[cfg(test)]
+use std::collections::VecDeque;
+
+use crate::bindings::{Actions, Bindings};
+
+/// Where the actions for each tick come from.
+pub trait InputSource {
+    /// The actions held during the next tick.
+    fn next(&mut self) -> Actions;
+}
+
+/// The actions bound to the keys held on coffee's keyboard.
+pub struct Keyboard {
+    bindings: Bindings,
+    held: Actions,
+}
+
+impl Keyboard {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            held: Actions::new(),
+        }
+    }
+
+    /// Reads which of the bound keys are currently pressed.
+    pub fn capture(&mut self, input: &KeyboardAndMouse) {
+        self.held = self.bindings.actions(input);
+    }
+
+    pub fn held(&self) -> &Actions {
+        &self.held
+    }
+}
+
+impl InputSource for Keyboard {
+    fn next(&mut self) -> Actions {
+        self.held.clone()
+    }
+}
+
+/// A fixed sequence of actions, each held for a number of ticks, followed by nothing at all.
+#[cfg(test)]
+#[derive(Default)]
+pub struct Scripted {
+    steps: VecDeque<(Actions, u32)>,
+}
+
+#[cfg(test)]
+impl Scripted {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hold(mut self, actions: Actions, ticks: u32) -> Self {
+        self.steps.push_back((actions, ticks));
+        self
+    }
+
+    pub fn wait(self, ticks: u32) -> Self {
+        self.hold(Actions::new(), ticks)
+    }
+}
+
+#[cfg(test)]
+impl InputSource for Scripted {
+    fn next(&mut self) -> Actions {
+        while let Some((actions, ticks)) = self.steps.front_mut() {
+            if *ticks > 0 {
+                *ticks -= 1;
+                return actions.clone();
+            }
+            self.steps.pop_front();
+        }
+        Actions::new()
+    }
+}
+
+/// Plays back the actions recorded by a `Recorder`, one tick at a time.
+#[cfg(test)]
+pub struct Recorded {
+    ticks: Vec<Actions>,
+    next: usize,
+}
+
+#[cfg(test)]
+impl Recorded {
+    pub fn new(ticks: Vec<Actions>) -> Self {
+        Self { ticks, next: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.ticks.len()
+    }
+}
+
+#[cfg(test)]
+impl InputSource for Recorded {
+    fn next(&mut self) -> Actions {
+        let actions = self.ticks.get(self.next).cloned().unwrap_or_default();
+        self.next += 1;
+        actions
+    }
+}
+
+/// Passes the actions from another source through, keeping a copy of each tick's so they can
+/// be played back with `Recorded`.
+#[cfg(test)]
+pub struct Recorder<S> {
+    source: S,
+    ticks: Vec<Actions>,
+}
+
+#[cfg(test)]
+impl<S: InputSource> Recorder<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            ticks: Vec::new(),
+        }
+    }
+
+    pub fn into_recording(self) -> Vec<Actions> {
+        self.ticks
+    }
+}
+
+#[cfg(test)]
+impl<S: InputSource> InputSource for Recorder<S> {
+    fn next(&mut self) -> Actions {
+        let actions = self.source.next();
+        self.ticks.push(actions.clone());
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::Action;
+    use crate::map::Map;
+    use crate::object::Object;
+    use crate::testing::{self, run, run_with};
+    use crate::world::World;
+
+    fn world() -> World<'static> {
+        testing::world(Map::default())
+    }
+
+    fn player(world: &World) -> Object {
+        let entity_manager = &world.entity_manager;
+        entity_manager
+            .by_name("player")
+            .and_then(|player| entity_manager.get_object(player))
+            .cloned()
+            .unwrap()
+    }
+
+    fn pressing(action: Action) -> Actions {
+        Actions::new().with(action)
+    }
+
+    #[test]
+    fn scripted_actions_are_held_for_their_ticks() {
+        let mut script = Scripted::new()
+            .hold(pressing(Action::MoveRight), 2)
+            .wait(1)
+            .hold(pressing(Action::Jump), 1);
+
+        assert_eq!(script.next(), pressing(Action::MoveRight));
+        assert_eq!(script.next(), pressing(Action::MoveRight));
+        assert_eq!(script.next(), Actions::new());
+        assert_eq!(script.next(), pressing(Action::Jump));
+        assert_eq!(script.next(), Actions::new());
+    }
+
+    #[test]
+    fn player_walks_in_the_direction_held() {
+        let mut world = world();
+        run(&mut world, 120);
+        let start = player(&world).rect.x;
+
+        run_with(
+            &mut world,
+            &mut Scripted::new().hold(pressing(Action::MoveRight), 20),
+            20,
+        );
+        let right = player(&world).rect.x;
+        assert!(right > start);
+
+        run_with(
+            &mut world,
+            &mut Scripted::new().hold(pressing(Action::MoveLeft), 40),
+            40,
+        );
+        assert!(player(&world).rect.x < right);
+    }
+
+    #[test]
+    fn player_jumps_off_the_ground() {
+        let mut world = world();
+        run(&mut world, 120);
+        let floor = player(&world).rect.y;
+
+        run_with(
+            &mut world,
+            &mut Scripted::new().hold(pressing(Action::Jump), 1),
+            5,
+        );
+        assert!(player(&world).rect.y < floor);
+
+        run(&mut world, 120);
+        assert_eq!(player(&world).rect.y, floor);
+    }
+
+    #[test]
+    fn recordings_play_back_identically() {
+        let script = Scripted::new()
+            .wait(30)
+            .hold(pressing(Action::MoveRight), 25)
+            .hold(pressing(Action::Jump), 2)
+            .hold(pressing(Action::MoveLeft), 10);
+
+        let mut recorded_world = world();
+        let mut recorder = Recorder::new(script);
+        run_with(&mut recorded_world, &mut recorder, 90);
+
+        let mut replayed_world = world();
+        let mut playback = Recorded::new(recorder.into_recording());
+        run_with(&mut replayed_world, &mut playback, 90);
+
+        assert!(playback.is_finished());
+        assert_eq!(player(&recorded_world).rect, player(&replayed_world).rect);
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+
+use crate::error::Error;
+
+/// A set of collision layers, as a bitmask.
+///
+/// Data files may instead list the layers by the names given in `assets/layers.json`, which
+/// are turned into bits with `Layers::bits` once loaded.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum LayerMask {
+    Bits(u32),
+    Names(Vec<String>),
+}
+
+impl LayerMask {
+    pub fn all() -> Self {
+        LayerMask::Bits(u32::max_value())
+    }
+
+    /// The bits, unless the layers are given by names which still need resolving.
+    pub fn bits(&self) -> Option<u32> {
+        match self {
+            LayerMask::Bits(bits) => Some(*bits),
+            LayerMask::Names(_) => None,
+        }
+    }
+}
+
+impl Default for LayerMask {
+    fn default() -> Self {
+        LayerMask::Bits(1)
+    }
+}
+
+/// The named collision layers, where each name is given the bit matching its position.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Layers {
+    layers: Vec<String>,
+}
+
+impl Layers {
+    pub fn new(layers: Vec<String>) -> Self {
+        Self { layers }
+    }
+
+    pub fn load() -> Result<Self, Error> {
+        let file = File::open("assets/layers.json")?;
+        let reader = io::BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn bit(&self, name: &str) -> Result<u32, Error> {
+        self.layers
+            .iter()
+            .take(32)
+            .position(|layer| layer == name)
+            .map(|index| 1 << index)
+            .ok_or_else(|| Error::UnknownLayer(name.to_string()))
+    }
+
+    /// The bits for the mask, looking up any names in it.
+    pub fn bits(&self, mask: &LayerMask) -> Result<u32, Error> {
+        match mask {
+            LayerMask::Bits(bits) => Ok(*bits),
+            LayerMask::Names(names) => {
+                let mut bits = 0;
+                for name in names {
+                    bits |= self.bit(name)?;
+                }
+                Ok(bits)
+            }
+        }
+    }
+
+    pub fn resolve(&self, mask: &LayerMask) -> Result<LayerMask, Error> {
+        Ok(LayerMask::Bits(self.bits(mask)?))
+    }
+}
@@ -1,16 +1,36 @@
 use coffee::graphics::WindowSettings;
 use coffee::Game;
 
+mod acceleration;
+mod ai;
 mod assets;
+mod bindings;
+mod broadphase;
 mod camera;
+mod component;
 mod config;
 mod entity;
 mod error;
+mod event;
 mod game;
+mod hierarchy;
 mod input;
+mod input_source;
+mod layers;
 mod map;
+mod navigation;
 mod object;
+mod platform;
+mod prefab;
+mod reachability;
 mod rect;
+mod save;
+mod surface;
+mod system;
+#[cfg(test)]
+mod testing;
+mod trigger;
+mod world;
 
 use crate::config::Config;
 pub use crate::error::Error;
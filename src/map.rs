@@ -1,4 +1,8 @@
-use crate::object::Object;
+use crate::error::Error;
+use crate::layers::Layers;
+#[cfg(test)]
+use crate::object::Shape;
+use crate::object::{Collision, Object};
 use crate::rect::Rect;
 use itertools::iproduct;
 use serde::{Deserialize, Serialize};
@@ -22,8 +26,14 @@ impl<'a> Cell<'a> {
         }
     }
 
-    pub fn collision(mut self, collides: bool) -> Self {
-        self.object = self.object.collision(collides);
+    pub fn collision(mut self, collision: Collision) -> Self {
+        self.object = self.object.collision(collision);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.object = self.object.shape(shape);
         self
     }
 
@@ -58,12 +68,51 @@ impl<'a> Default for Cell<'a> {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// An entity placed in the map, made from a prefab when the map is loaded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Spawn {
+    pub prefab: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Fields merged over the prefab's, so that this entity can differ from the others made
+    /// from the same prefab.
+    #[serde(default)]
+    pub overrides: Option<serde_json::Value>,
+}
+
+impl Spawn {
+    pub fn new<S>(prefab: S, x: f32, y: f32) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            prefab: prefab.into(),
+            x,
+            y,
+            name: None,
+            overrides: None,
+        }
+    }
+
+    pub fn with_name<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Map<'a> {
     cells: Vec<Cell<'a>>,
     pub width: u16,
     pub height: u16,
     tilesize: u16,
+    #[serde(default)]
+    pub entities: Vec<Spawn>,
 }
 
 impl<'a> Default for Map<'a> {
@@ -75,7 +124,7 @@ impl<'a> Default for Map<'a> {
         let wall = Cell::with_size(size, size).with_asset("box");
         let empty = Cell::with_size(size, size)
             .with_asset("dirtCenter")
-            .collision(false);
+            .collision(Collision::None);
 
         for y in 0..height {
             for x in 0..width {
@@ -91,12 +140,13 @@ impl<'a> Default for Map<'a> {
             width: width,
             height: height,
             tilesize: size as u16,
+            entities: vec![Spawn::new("player", 100.0, 100.0).with_name("player")],
         }
     }
 }
 
 impl<'a> Map<'a> {
-    pub fn load() -> Self {
+    pub fn load(layers: &Layers) -> Self {
         let path = Path::new("assets/map.map");
         if !path.exists() {
             let file = File::create(path).unwrap();
@@ -106,7 +156,118 @@ impl<'a> Map<'a> {
 
         let file = File::open("assets/map.map").unwrap();
         let reader = io::BufReader::new(file);
-        serde_json::from_reader(reader).unwrap()
+        let mut map: Map = serde_json::from_reader(reader).unwrap();
+        map.resolve_layers(layers).unwrap();
+        map
+    }
+
+    /// Looks up the bits for any layer names in the tiles, so they're ready to be collided.
+    pub fn resolve_layers(&mut self, layers: &Layers) -> Result<(), Error> {
+        for cell in self.cells.iter_mut() {
+            cell.object.resolve_layers(layers)?;
+        }
+        Ok(())
+    }
+
+    /// Builds a map of 70px tiles from rows of characters, one per tile: `#` is a solid tile,
+    /// `=` a one way platform, `/` and `\` the left and right sides of a hill, `l` and `L` the
+    /// low and high tiles of a gentle hill's left side, `R` and `r` those of its right side,
+    /// and anything else is empty. `@`, `E` and `C` also spawn the player, an exit and a coin
+    /// at the top left of their tiles. Leading and trailing whitespace and blank lines are
+    /// ignored, and short rows are padded out with empty tiles.
+    #[cfg(test)]
+    pub fn from_ascii(ascii: &str) -> Self {
+        let size = 70.0;
+        let rows: Vec<&str> = ascii
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let height = rows.len();
+
+        let mut cells = Vec::new();
+        let mut entities = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            let mut tiles = row.chars();
+            for x in 0..width {
+                let (left, top) = (x as f32 * size, y as f32 * size);
+                let tile = tiles.next();
+                match tile {
+                    Some('@') => entities.push(Spawn::new("player", left, top).with_name("player")),
+                    Some('E') => entities.push(Spawn::new("exit", left, top)),
+                    Some('C') => entities.push(Spawn::new("coin", left, top)),
+                    _ => (),
+                }
+
+                let cell = match tile {
+                    Some('#') => Cell::with_size(size, size).with_asset("box"),
+                    Some('=') => Cell::with_size(size, size)
+                        .with_asset("bridge")
+                        .collision(Collision::OneWayUp),
+                    Some('/') => Cell::with_size(size, size)
+                        .with_asset("dirtHillLeft")
+                        .shape(Shape::SlopeLeft),
+                    Some('\\') => Cell::with_size(size, size)
+                        .with_asset("dirtHillRight")
+                        .shape(Shape::SlopeRight),
+                    Some('l') => Cell::with_size(size, size)
+                        .with_asset("dirtHillLeft")
+                        .shape(Shape::GentleSlopeLeftLow),
+                    Some('L') => Cell::with_size(size, size)
+                        .with_asset("dirtHillLeft")
+                        .shape(Shape::GentleSlopeLeftHigh),
+                    Some('R') => Cell::with_size(size, size)
+                        .with_asset("dirtHillRight")
+                        .shape(Shape::GentleSlopeRightHigh),
+                    Some('r') => Cell::with_size(size, size)
+                        .with_asset("dirtHillRight")
+                        .shape(Shape::GentleSlopeRightLow),
+                    _ => Cell::with_size(size, size)
+                        .with_asset("dirtCenter")
+                        .collision(Collision::None),
+                };
+                cells.push(cell.at(left, top));
+            }
+        }
+
+        Self {
+            cells,
+            width: width as u16,
+            height: height as u16,
+            tilesize: size as u16,
+            entities,
+        }
+    }
+
+    pub fn get_tilesize(&self) -> f32 {
+        self.tilesize as f32
+    }
+
+    /// The cell at the tile position, if it's within the map.
+    pub fn get_cell(&self, x: u16, y: u16) -> Option<&Cell<'a>> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.cells
+            .get(y as usize * self.width as usize + x as usize)
+    }
+
+    /// The collision of the tile at the position. Beyond the sides and top of the map count as
+    /// solid, and below the bottom as empty, since that's where entities fall out of the map.
+    pub fn collision_at(&self, x: i32, y: i32) -> Collision {
+        if y >= self.height as i32 {
+            return Collision::None;
+        }
+        if x < 0 || y < 0 {
+            return Collision::Solid;
+        }
+        self.get_cell(x as u16, y as u16)
+            .map_or(Collision::Solid, |cell| cell.object.get_collision())
     }
 
     pub fn iter(&'a self) -> IterMap<'a> {
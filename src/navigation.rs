@@ -0,0 +1,490 @@
+use nalgebra::Point2;
+use std::cmp::{self, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+use crate::input::JUMP_FORCE;
+use crate::map::Map;
+use crate::object::{Collision, Movement};
+
+/// A tile position in a map, as `(x, y)`.
+pub type Tile = (u16, u16);
+
+/// How an entity gets from one standing spot to the next.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Move {
+    /// To the neighbouring tile at the same height.
+    Walk,
+    /// Off the edge of whatever it's standing on, landing on the first ground below.
+    Fall,
+    Jump,
+}
+
+/// A move from one standing spot to another, and how costly it is.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Edge {
+    pub to: Tile,
+    pub kind: Move,
+    pub cost: u32,
+}
+
+/// One step along a path, made by moving onto the tile.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Step {
+    pub tile: Tile,
+    pub kind: Move,
+}
+
+/// How much room an entity needs and how far it can jump, in tiles.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Reach {
+    /// How many tiles tall the entity is.
+    pub clearance: u16,
+    /// How many tiles up the entity can jump.
+    pub jump_height: u16,
+    /// How many tiles across the entity can jump, landing at the height it jumped from.
+    pub jump_distance: u16,
+}
+
+impl Reach {
+    /// The reach of an entity of the height, jumping with `JUMP_FORCE` and moving with the
+    /// movement at up to `speed`.
+    pub fn new(height: f32, movement: &Movement, speed: f32, tilesize: f32) -> Self {
+        let (max_x, max_y) = movement.get_max_speed();
+        let gravity = movement.get_constant_force().y;
+        let jump_speed = max_y.map_or(-JUMP_FORCE, |max| f32::min(-JUMP_FORCE, max));
+        let speed = max_x.map_or(speed, |max| f32::min(speed, max));
+
+        // Without gravity an entity can't jump, since it never comes back down.
+        let (jump_height, air_time) = if gravity > 0.0 {
+            (
+                jump_speed * jump_speed / (2.0 * gravity),
+                2.0 * jump_speed / gravity,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        Self {
+            clearance: (height / tilesize).ceil() as u16,
+            jump_height: (jump_height / tilesize) as u16,
+            jump_distance: (speed * air_time / tilesize) as u16,
+        }
+    }
+}
+
+/// The spots in a map that an entity can stand on, and the moves between them.
+///
+/// A standing spot is an empty tile with ground directly beneath it and room above it for the
+/// entity. Entities are assumed to be no wider than a tile, and slopes are treated as if they
+/// were full tiles.
+pub struct NavGraph {
+    edges: BTreeMap<Tile, Vec<Edge>>,
+    tilesize: f32,
+}
+
+impl NavGraph {
+    pub fn new(map: &Map, reach: Reach) -> Self {
+        let grid = Grid { map, reach };
+        let mut edges = BTreeMap::new();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                if grid.is_standing_spot(x, y) {
+                    edges.insert((x, y), grid.edges_from(x, y));
+                }
+            }
+        }
+
+        Self {
+            edges,
+            tilesize: map.get_tilesize(),
+        }
+    }
+
+    pub fn is_standing_spot(&self, tile: Tile) -> bool {
+        self.edges.contains_key(&tile)
+    }
+
+    /// Every move which can be made from the tile.
+    pub fn edges(&self, tile: Tile) -> &[Edge] {
+        self.edges.get(&tile).map_or(&[], Vec::as_slice)
+    }
+
+    /// The standing spot that something at the point would end up on, by falling straight
+    /// down from the tile the point is in.
+    pub fn tile_at(&self, point: Point2<f32>) -> Option<Tile> {
+        if point.x < 0.0 || point.y < 0.0 {
+            return None;
+        }
+
+        let x = (point.x / self.tilesize) as u16;
+        let y = (point.y / self.tilesize) as u16;
+        self.edges
+            .range((x, y)..=(x, u16::max_value()))
+            .next()
+            .map(|(tile, _)| *tile)
+    }
+
+    /// The point at the middle of the bottom of the tile, where the feet of an entity standing
+    /// on it would be.
+    pub fn feet(&self, tile: Tile) -> Point2<f32> {
+        Point2::new(
+            (tile.0 as f32 + 0.5) * self.tilesize,
+            (tile.1 as f32 + 1.0) * self.tilesize,
+        )
+    }
+
+    /// The cheapest path between the standing spots below the points. See `find_path`.
+    pub fn find_path_between(&self, from: Point2<f32>, to: Point2<f32>) -> Option<Vec<Step>> {
+        self.find_path(self.tile_at(from)?, self.tile_at(to)?)
+    }
+
+    /// The cheapest path from one standing spot to another using A*, as the steps taken after
+    /// leaving `from`. Returns `None` if either tile isn't a standing spot, or if there's no way
+    /// to get between them.
+    pub fn find_path(&self, from: Tile, to: Tile) -> Option<Vec<Step>> {
+        if !self.is_standing_spot(from) || !self.is_standing_spot(to) {
+            return None;
+        }
+
+        // Every move costs at least the tiles it covers, so this never overestimates.
+        let estimate = |tile: Tile| distance(tile.0, to.0) + distance(tile.1, to.1);
+
+        let mut open = BinaryHeap::new();
+        let mut costs: HashMap<Tile, u32> = HashMap::new();
+        let mut came_from: HashMap<Tile, (Tile, Move)> = HashMap::new();
+        open.push(Reverse((estimate(from), 0, from)));
+        costs.insert(from, 0);
+
+        while let Some(Reverse((_, cost, tile))) = open.pop() {
+            if tile == to {
+                let mut steps = Vec::new();
+                let mut current = to;
+                while let Some((previous, kind)) = came_from.get(&current) {
+                    steps.push(Step {
+                        tile: current,
+                        kind: *kind,
+                    });
+                    current = *previous;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
+
+            if costs.get(&tile).map_or(false, |best| cost > *best) {
+                continue;
+            }
+
+            for edge in self.edges(tile) {
+                let next_cost = cost + edge.cost;
+                if costs.get(&edge.to).map_or(true, |best| next_cost < *best) {
+                    costs.insert(edge.to, next_cost);
+                    came_from.insert(edge.to, (tile, edge.kind));
+                    open.push(Reverse((next_cost + estimate(edge.to), next_cost, edge.to)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The navigation graphs for a map, each built the first time an entity with its reach needs
+/// it.
+pub struct NavGraphs {
+    graphs: HashMap<Reach, NavGraph>,
+}
+
+impl NavGraphs {
+    pub fn new() -> Self {
+        Self {
+            graphs: HashMap::new(),
+        }
+    }
+
+    /// The graph for entities with the reach. Every call must pass the same map.
+    pub fn get(&mut self, map: &Map, reach: Reach) -> &NavGraph {
+        self.graphs
+            .entry(reach)
+            .or_insert_with(|| NavGraph::new(map, reach))
+    }
+}
+
+/// The map as seen by an entity with the reach, for working out the moves between tiles.
+struct Grid<'m> {
+    map: &'m Map<'m>,
+    reach: Reach,
+}
+
+impl<'m> Grid<'m> {
+    /// Whether the entity fits with its feet in the tile. When jumping, the entity can pass up
+    /// through one way platforms as well as empty tiles.
+    fn fits(&self, x: i32, y: i32, jumping: bool) -> bool {
+        (0..self.reach.clearance as i32).all(|above| match self.map.collision_at(x, y - above) {
+            Collision::None => true,
+            Collision::OneWayUp => jumping,
+            Collision::Solid => false,
+        })
+    }
+
+    fn is_standing_spot(&self, x: u16, y: u16) -> bool {
+        let (x, y) = (x as i32, y as i32);
+        self.fits(x, y, false) && self.map.collision_at(x, y + 1) != Collision::None
+    }
+
+    fn edges_from(&self, x: u16, y: u16) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        for &direction in &[-1, 1] {
+            let next = x as i32 + direction;
+            if next < 0 || next >= self.map.width as i32 {
+                continue;
+            }
+            let next = next as u16;
+
+            if self.is_standing_spot(next, y) {
+                edges.push(Edge {
+                    to: (next, y),
+                    kind: Move::Walk,
+                    cost: 1,
+                });
+            } else if self.fits(next as i32, y as i32, false) {
+                let landing = (y + 1..self.map.height)
+                    .take_while(|below| self.fits(next as i32, *below as i32, false))
+                    .find(|below| self.is_standing_spot(next, *below));
+                if let Some(landing) = landing {
+                    edges.push(Edge {
+                        to: (next, landing),
+                        kind: Move::Fall,
+                        cost: 1 + u32::from(landing - y),
+                    });
+                }
+            }
+        }
+
+        edges.extend(self.jumps_from(x, y));
+        edges
+    }
+
+    /// The jumps to standing spots in reach which can't simply be walked or fallen to.
+    ///
+    /// A jump is taken to rise straight up to just above the higher of the two spots, cross
+    /// over at that height and then drop straight down, so that's the room it needs.
+    fn jumps_from(&self, x: u16, y: u16) -> Vec<Edge> {
+        let reach = self.reach;
+        let min_x = x.saturating_sub(reach.jump_distance);
+        let max_x = cmp::min(x + reach.jump_distance, self.map.width - 1);
+        let min_y = y.saturating_sub(reach.jump_height);
+
+        let mut jumps = Vec::new();
+        for to_y in min_y..self.map.height {
+            for to_x in min_x..=max_x {
+                let dx = distance(x, to_x);
+                if (dx <= 1 && to_y >= y) || !self.is_standing_spot(to_x, to_y) {
+                    continue;
+                }
+
+                let apex = cmp::max(cmp::min(y, to_y).saturating_sub(1), min_y);
+                if self.has_room_to_jump(x, y, to_x, to_y, apex) {
+                    jumps.push(Edge {
+                        to: (to_x, to_y),
+                        kind: Move::Jump,
+                        cost: 1 + dx + distance(y, to_y),
+                    });
+                }
+            }
+        }
+        jumps
+    }
+
+    fn has_room_to_jump(&self, x: u16, y: u16, to_x: u16, to_y: u16, apex: u16) -> bool {
+        let fits = |x: u16, y: u16| self.fits(x as i32, y as i32, true);
+        (apex..=y).all(|row| fits(x, row))
+            && (cmp::min(x, to_x)..=cmp::max(x, to_x)).all(|column| fits(column, apex))
+            && (apex..=to_y).all(|row| fits(to_x, row))
+    }
+}
+
+fn distance(a: u16, b: u16) -> u32 {
+    (a as i32 - b as i32).abs() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector2;
+
+    fn graph(ascii: &str, reach: Reach) -> NavGraph {
+        NavGraph::new(&Map::from_ascii(ascii), reach)
+    }
+
+    fn small() -> Reach {
+        Reach {
+            clearance: 1,
+            jump_height: 2,
+            jump_distance: 3,
+        }
+    }
+
+    fn moves(path: &[Step]) -> Vec<Move> {
+        path.iter().map(|step| step.kind).collect()
+    }
+
+    #[test]
+    fn reach_comes_from_the_movement_limits() {
+        let movement = Movement::new()
+            .with_max_speed((Some(10.0), Some(20.0)))
+            .with_force(Vector2::new(0.0, 0.5));
+
+        assert_eq!(
+            Reach::new(106.0, &movement, 6.0, 70.0),
+            Reach {
+                clearance: 2,
+                jump_height: 3,
+                jump_distance: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn walks_along_the_floor() {
+        let graph = graph(
+            "
+            ......
+            ######
+            ",
+            small(),
+        );
+
+        let path = graph.find_path((0, 0), (5, 0)).unwrap();
+        assert_eq!(moves(&path), vec![Move::Walk; 5]);
+        assert_eq!(path.last().unwrap().tile, (5, 0));
+    }
+
+    #[test]
+    fn falls_off_ledges() {
+        let graph = graph(
+            "
+            ......
+            ##....
+            ##....
+            ######
+            ",
+            small(),
+        );
+
+        let path = graph.find_path((1, 0), (5, 2)).unwrap();
+        assert_eq!(
+            path[0],
+            Step {
+                tile: (2, 2),
+                kind: Move::Fall
+            }
+        );
+        // Getting back up again takes a jump.
+        let path = graph.find_path((5, 2), (1, 0)).unwrap();
+        assert_eq!(path.last().unwrap().kind, Move::Jump);
+    }
+
+    #[test]
+    fn jumps_up_walls_within_reach() {
+        let ascii = "
+            .....
+            ...##
+            ...##
+            #####
+            ";
+
+        let path = graph(ascii, small()).find_path((0, 2), (4, 0)).unwrap();
+        assert!(moves(&path).contains(&Move::Jump));
+
+        let short = Reach {
+            jump_height: 1,
+            ..small()
+        };
+        assert_eq!(graph(ascii, short).find_path((0, 2), (4, 0)), None);
+    }
+
+    #[test]
+    fn jumps_gaps_within_reach() {
+        let narrow = graph(
+            "
+            ......
+            ##..##
+            ",
+            small(),
+        );
+        let path = narrow.find_path((0, 0), (5, 0)).unwrap();
+        assert!(moves(&path).contains(&Move::Jump));
+
+        let wide = "
+            ........
+            ##....##
+            ";
+        assert_eq!(graph(wide, small()).find_path((0, 0), (7, 0)), None);
+
+        let long = Reach {
+            jump_distance: 5,
+            ..small()
+        };
+        assert!(graph(wide, long).find_path((0, 0), (7, 0)).is_some());
+    }
+
+    #[test]
+    fn jumps_up_through_one_way_platforms() {
+        let graph = graph(
+            "
+            .....
+            .===.
+            .....
+            #####
+            ",
+            small(),
+        );
+
+        assert!(graph.is_standing_spot((2, 0)));
+        let path = graph.find_path((2, 2), (2, 0)).unwrap();
+        assert_eq!(
+            path,
+            vec![Step {
+                tile: (2, 0),
+                kind: Move::Jump
+            }]
+        );
+    }
+
+    #[test]
+    fn tall_entities_need_room_to_stand() {
+        let ascii = "
+            ......
+            ..####
+            ......
+            ######
+            ";
+        let tall = Reach {
+            clearance: 2,
+            ..small()
+        };
+
+        assert!(graph(ascii, small()).find_path((0, 2), (5, 2)).is_some());
+        assert!(!graph(ascii, tall).is_standing_spot((3, 2)));
+        assert_eq!(graph(ascii, tall).find_path((0, 2), (5, 2)), None);
+    }
+
+    #[test]
+    fn points_drop_to_the_spot_below() {
+        let graph = graph(
+            "
+            ....
+            ....
+            ####
+            ",
+            small(),
+        );
+
+        assert_eq!(graph.tile_at(Point2::new(150.0, 10.0)), Some((2, 1)));
+        assert_eq!(graph.feet((2, 1)), Point2::new(175.0, 140.0));
+        assert_eq!(
+            graph.find_path_between(Point2::new(10.0, 10.0), Point2::new(250.0, 10.0)),
+            graph.find_path((0, 1), (3, 1))
+        );
+    }
+}
@@ -1,12 +1,21 @@
+use crate::error::Error;
+use crate::layers::{LayerMask, Layers};
 use crate::rect::Rect;
+use crate::surface::Surface;
 use nalgebra::Vector2;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Movement {
     speed: Vector2<f32>,
     forces: Vec<Vector2<f32>>,
     instantaneous_forces: Vec<Vector2<f32>>,
     max_speed: (Option<f32>, Option<f32>),
+    grounded: bool,
+    surface: Surface,
+    /// Whether the horizontal speed has been steered this tick, in place of friction.
+    #[serde(default)]
+    driven: bool,
     dirty: bool,
 }
 
@@ -17,6 +26,9 @@ impl Movement {
             forces: Vec::new(),
             instantaneous_forces: Vec::new(),
             max_speed: (None, None),
+            grounded: false,
+            surface: Surface::default(),
+            driven: false,
             dirty: true,
         }
     }
@@ -51,7 +63,11 @@ impl Movement {
 
         self.instantaneous_forces.clear();
 
-        let drag = -1.0 * self.speed.x.signum() * 0.4;
+        if self.driven {
+            return;
+        }
+
+        let drag = -1.0 * self.speed.x.signum() * self.surface.friction;
         self.speed.x = if drag.abs() <= self.speed.x.abs() {
             self.speed.x + drag
         } else {
@@ -76,13 +92,77 @@ impl Movement {
         self.dirty = true;
     }
 
-    pub fn reset_speed(&mut self) {
-        self.speed = Vector2::new(0.0, 0.0);
+    /// Steers the horizontal speed towards the target by up to `rate`. Friction is left out
+    /// for the rest of the tick, since the rate already accounts for it.
+    pub fn drive(&mut self, target: f32, rate: f32) {
+        let difference = target - self.speed.x;
+        self.speed.x = if difference.abs() <= rate {
+            target
+        } else {
+            self.speed.x + difference.signum() * rate
+        };
+        self.driven = true;
+        self.dirty = true;
+    }
+
+    /// Lets friction apply again, until the speed is next steered.
+    pub fn end_tick(&mut self) {
+        self.driven = false;
+    }
+
+    pub fn get_speed(&self) -> Vector2<f32> {
+        self.speed
+    }
+
+    pub fn get_max_speed(&self) -> (Option<f32>, Option<f32>) {
+        self.max_speed
+    }
+
+    /// The total of the constant forces acting on the movement, such as gravity.
+    pub fn get_constant_force(&self) -> Vector2<f32> {
+        self.forces.iter().sum()
+    }
+
+    pub fn set_grounded(&mut self, grounded: bool) {
+        self.grounded = grounded;
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Sets the surface the object is standing on, or `None` when it's in the air.
+    pub fn set_surface(&mut self, surface: Option<Surface>) {
+        self.surface = surface.unwrap_or_default();
+    }
+
+    /// The surface the object is standing on, if it's on the ground.
+    pub fn get_surface(&self) -> Option<Surface> {
+        if self.grounded {
+            Some(self.surface)
+        } else {
+            None
+        }
+    }
+
+    /// Bounces the object back up off the ground, keeping `restitution` of its falling speed.
+    pub fn bounce(&mut self, restitution: f32) {
+        self.speed.y = -1.0 * self.speed.y.abs() * restitution;
+    }
+
+    /// Stops the object moving along the axes it has run into something on.
+    pub fn stop(&mut self, horizontal: bool, vertical: bool) {
+        if horizontal {
+            self.speed.x = 0.0;
+        }
+        if vertical {
+            self.speed.y = 0.0;
+        }
     }
 
     pub fn dx(&mut self) -> f32 {
         self.update();
-        self.speed.x
+        self.speed.x + self.surface.conveyor
     }
 
     pub fn dy(&mut self) -> f32 {
@@ -91,11 +171,81 @@ impl Movement {
     }
 }
 
+/// How an `Object` blocks the objects that run into it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Collision {
+    Solid,
+    None,
+    /// Only blocks objects falling onto it from above, so they can jump up through it.
+    OneWayUp,
+}
+
+/// The part of an `Object`'s rect that is actually collided against.
+///
+/// Slopes are named after the hill tiles in the atlas: a `SlopeLeft` is the left side of a
+/// hill, so it rises from the bottom left corner towards the right. The gentle slopes rise by
+/// half a tile each, so a low and a high tile are placed side by side to make a full hill.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Shape {
+    Full,
+    Half,
+    SlopeLeft,
+    SlopeRight,
+    GentleSlopeLeftLow,
+    GentleSlopeLeftHigh,
+    GentleSlopeRightLow,
+    GentleSlopeRightHigh,
+}
+
+impl Shape {
+    pub fn is_slope(self) -> bool {
+        match self {
+            Shape::Full | Shape::Half => false,
+            _ => true,
+        }
+    }
+
+    /// The height of the surface above the bottom of the rect, as a fraction of its height,
+    /// at `t` (0.0 on the left edge to 1.0 on the right edge).
+    fn height_at(self, t: f32) -> f32 {
+        match self {
+            Shape::Full => 1.0,
+            Shape::Half => 0.5,
+            Shape::SlopeLeft => t,
+            Shape::SlopeRight => 1.0 - t,
+            Shape::GentleSlopeLeftLow => t / 2.0,
+            Shape::GentleSlopeLeftHigh => 0.5 + t / 2.0,
+            Shape::GentleSlopeRightLow => 0.5 - t / 2.0,
+            Shape::GentleSlopeRightHigh => 1.0 - t / 2.0,
+        }
+    }
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape::Full
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Object {
     pub rect: Rect<f32>,
     pub visible: bool,
-    is_solid: bool,
+    /// Older map files have an `is_solid` flag here instead.
+    #[serde(alias = "is_solid", deserialize_with = "deserialize_collision")]
+    collision: Collision,
+    #[serde(default)]
+    shape: Shape,
+    /// The layers this object is on.
+    #[serde(default)]
+    layer: LayerMask,
+    /// The layers this object collides with.
+    #[serde(default = "LayerMask::all")]
+    mask: LayerMask,
+    /// The layer and mask as bits, once any names in them have been resolved. The names are
+    /// kept as they are, so they're still there when the object is written back out.
+    #[serde(skip)]
+    resolved: Option<(u32, u32)>,
 }
 
 impl Object {
@@ -104,7 +254,11 @@ impl Object {
         Self {
             rect: Rect::new(x, y, width, height),
             visible: true,
-            is_solid: true,
+            collision: Collision::Solid,
+            shape: Shape::Full,
+            layer: LayerMask::default(),
+            mask: LayerMask::all(),
+            resolved: None,
         }
     }
 
@@ -112,15 +266,88 @@ impl Object {
         Self {
             rect: Rect::default().size(width, height),
             visible: true,
-            is_solid: true,
+            collision: Collision::Solid,
+            shape: Shape::Full,
+            layer: LayerMask::default(),
+            mask: LayerMask::all(),
+            resolved: None,
         }
     }
 
-    pub fn collision(mut self, is_solid: bool) -> Self {
-        self.is_solid = is_solid;
+    pub fn collision(mut self, collision: Collision) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    pub fn get_collision(&self) -> Collision {
+        self.collision
+    }
+
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn get_shape(&self) -> Shape {
+        self.shape
+    }
+
+    /// Sets the layers. Any names in them must be resolved with `resolve_layers` before the
+    /// object collides with anything.
+    pub fn layers(mut self, layer: LayerMask, mask: LayerMask) -> Self {
+        self.layer = layer;
+        self.mask = mask;
+        self.resolved = None;
         self
     }
 
+    /// Looks up the bits for any layer names, so they're ready to be collided.
+    pub fn resolve_layers(&mut self, layers: &Layers) -> Result<(), Error> {
+        self.resolved = Some((layers.bits(&self.layer)?, layers.bits(&self.mask)?));
+        Ok(())
+    }
+
+    /// The layer and mask as bits, or `None` if either is given by names which haven't been
+    /// resolved.
+    fn layer_bits(&self) -> Option<(u32, u32)> {
+        match (self.resolved, self.layer.bits(), self.mask.bits()) {
+            (Some(bits), _, _) => Some(bits),
+            (None, Some(layer), Some(mask)) => Some((layer, mask)),
+            _ => None,
+        }
+    }
+
+    /// Whether each object is on one of the layers that the other collides with. Objects whose
+    /// layer names haven't been resolved don't collide with anything.
+    pub fn collides_with(&self, other: &Self) -> bool {
+        match (self.layer_bits(), other.layer_bits()) {
+            (Some((layer, mask)), Some((other_layer, other_mask))) => {
+                layer & other_mask != 0 && other_layer & mask != 0
+            }
+            _ => false,
+        }
+    }
+
+    /// The rect that other objects collide against, which for half tiles is only the bottom
+    /// half. Slopes still report their full rect; use `floor_at` to find their surface.
+    pub fn collision_rect(&self) -> Rect<f32> {
+        match self.shape {
+            Shape::Half => Rect::new(
+                self.rect.x,
+                self.rect.y + self.rect.height / 2.0,
+                self.rect.width,
+                self.rect.height / 2.0,
+            ),
+            _ => self.rect,
+        }
+    }
+
+    /// The y coordinate of the top surface of the object at the given x coordinate.
+    pub fn floor_at(&self, x: f32) -> f32 {
+        let t = f32::min(f32::max((x - self.rect.x) / self.rect.width, 0.0), 1.0);
+        self.rect.y + self.rect.height * (1.0 - self.shape.height_at(t))
+    }
+
     pub fn at(mut self, x: f32, y: f32) -> Self {
         self.rect.x = x;
         self.rect.y = y;
@@ -138,12 +365,40 @@ impl Object {
         self.rect.y += y;
     }
 
+    /// Whether the two objects touch, regardless of how either of them collides.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.rect.has_overlap(&other.rect)
+    }
+
     pub fn overlap(&self, other: &Self) -> Option<Rect<f32>> {
-        if !self.is_solid || !other.is_solid {
+        if self.collision == Collision::None || other.collision == Collision::None {
             return None;
         }
-        self.rect.overlap(&other.rect)
+
+        if !self.collides_with(other) {
+            return None;
+        }
+        self.collision_rect().overlap(&other.collision_rect())
+    }
+}
+
+/// Reads a `Collision`, or the `is_solid` flag it replaced.
+fn deserialize_collision<'de, D>(deserializer: D) -> Result<Collision, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CollisionOrSolid {
+        Collision(Collision),
+        IsSolid(bool),
     }
+
+    Ok(match CollisionOrSolid::deserialize(deserializer)? {
+        CollisionOrSolid::Collision(collision) => collision,
+        CollisionOrSolid::IsSolid(true) => Collision::Solid,
+        CollisionOrSolid::IsSolid(false) => Collision::None,
+    })
 }
 
 impl Default for Object {
@@ -151,7 +406,80 @@ impl Default for Object {
         Self {
             rect: Rect::default(),
             visible: true,
-            is_solid: true,
+            collision: Collision::Solid,
+            shape: Shape::Full,
+            layer: LayerMask::default(),
+            mask: LayerMask::all(),
+            resolved: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn old_object(is_solid: bool) -> Object {
+        serde_json::from_str(&format!(
+            r#"{{
+                "rect": {{ "x": 70.0, "y": 0.0, "width": 70.0, "height": 70.0 }},
+                "visible": true,
+                "is_solid": {}
+            }}"#,
+            is_solid
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn objects_from_before_collision_modes_still_load() {
+        assert_eq!(old_object(true).get_collision(), Collision::Solid);
+        assert_eq!(old_object(false).get_collision(), Collision::None);
+        assert_eq!(old_object(true).rect, Rect::new(70.0, 0.0, 70.0, 70.0));
+    }
+
+    fn named_object(layer: &str) -> Object {
+        serde_json::from_str(&format!(
+            r#"{{
+                "rect": {{ "x": 0.0, "y": 0.0, "width": 70.0, "height": 70.0 }},
+                "visible": true,
+                "collision": "Solid",
+                "layer": ["{}"],
+                "mask": ["default", "player"]
+            }}"#,
+            layer
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn layer_names_are_kept_once_resolved() {
+        let layers = Layers::new(vec!["default".into(), "player".into(), "enemy".into()]);
+        let mut enemy = named_object("enemy");
+        let mut other = named_object("enemy");
+        enemy.resolve_layers(&layers).unwrap();
+        other.resolve_layers(&layers).unwrap();
+
+        assert!(!enemy.collides_with(&other));
+        assert!(enemy.collides_with(&Object::with_size(70.0, 70.0)));
+
+        let json = serde_json::to_value(&enemy).unwrap();
+        assert_eq!(json["layer"], serde_json::json!(["enemy"]));
+        assert_eq!(json["mask"], serde_json::json!(["default", "player"]));
+    }
+
+    #[test]
+    fn unresolved_layer_names_collide_with_nothing() {
+        let enemy = named_object("enemy");
+        assert!(!enemy.collides_with(&Object::with_size(70.0, 70.0)));
+        assert!(!Object::with_size(70.0, 70.0).collides_with(&enemy));
+    }
+
+    #[test]
+    fn collision_modes_round_trip() {
+        let object = Object::with_size(70.0, 70.0).collision(Collision::OneWayUp);
+        let json = serde_json::to_string(&object).unwrap();
+        let loaded: Object = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.get_collision(), Collision::OneWayUp);
+    }
+}
@@ -0,0 +1,205 @@
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+/// What a `Platform` does once it reaches the last of its waypoints.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PathMode {
+    /// Stop at the last waypoint.
+    Linear,
+    /// Turn around and travel back through the waypoints in reverse.
+    PingPong,
+    /// Travel from the last waypoint straight back to the first.
+    Loop,
+}
+
+/// A kinematic entity which moves its `Object` along a path of waypoints at a fixed speed.
+///
+/// Waypoints are positions for the top left corner of the object's rect.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Platform {
+    waypoints: Vec<Point2<f32>>,
+    mode: PathMode,
+    speed: f32,
+    next: usize,
+    reversed: bool,
+}
+
+impl Platform {
+    pub fn new(waypoints: Vec<Point2<f32>>) -> Self {
+        Self {
+            waypoints,
+            mode: PathMode::PingPong,
+            speed: 1.0,
+            next: 0,
+            reversed: false,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: PathMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Advances along the path from `position`, returning how far the platform should move
+    /// this tick.
+    ///
+    /// The distance is measured from wherever the platform actually is, so a platform which
+    /// was held back (for example, because it would have crushed an entity) simply resumes.
+    pub fn step(&mut self, position: Point2<f32>) -> Vector2<f32> {
+        let mut current = position;
+        let mut remaining = self.speed;
+
+        // Bounded so a path whose waypoints are all in the same spot can't spin forever.
+        for _ in 0..=self.waypoints.len() * 2 {
+            let target = match self.waypoints.get(self.next) {
+                Some(target) => *target,
+                None => break,
+            };
+
+            let distance = (target - current).norm();
+            if distance > remaining {
+                current += (target - current) * (remaining / distance);
+                break;
+            }
+
+            current = target;
+            remaining -= distance;
+            if !self.advance() {
+                break;
+            }
+        }
+
+        current - position
+    }
+
+    /// Picks the next waypoint to head for, returning false once the path is finished.
+    fn advance(&mut self) -> bool {
+        let last = self.waypoints.len().saturating_sub(1);
+        match self.mode {
+            PathMode::Linear => {
+                if self.next >= last {
+                    return false;
+                }
+                self.next += 1;
+            }
+            PathMode::Loop => {
+                self.next = if self.next >= last { 0 } else { self.next + 1 };
+            }
+            PathMode::PingPong => {
+                if last == 0 {
+                    return false;
+                }
+                if self.reversed && self.next == 0 {
+                    self.reversed = false;
+                } else if !self.reversed && self.next >= last {
+                    self.reversed = true;
+                }
+
+                if self.reversed {
+                    self.next -= 1;
+                } else {
+                    self.next += 1;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+    use crate::testing::{self, run};
+
+    /// The corners of a square, 10 on each side.
+    fn square() -> Vec<Point2<f32>> {
+        vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(10.0, 0.0),
+            Point2::new(10.0, 10.0),
+            Point2::new(0.0, 10.0),
+        ]
+    }
+
+    /// Where the platform is after each step, starting from the first waypoint.
+    fn positions(mut platform: Platform, steps: usize) -> Vec<(f32, f32)> {
+        let mut position = Point2::new(0.0, 0.0);
+        (0..steps)
+            .map(|_| {
+                position += platform.step(position);
+                (position.x, position.y)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn linear_paths_stop_at_the_last_waypoint() {
+        let platform = Platform::new(square())
+            .with_mode(PathMode::Linear)
+            .with_speed(5.0);
+        assert_eq!(
+            positions(platform, 8),
+            vec![
+                (5.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 5.0),
+                (10.0, 10.0),
+                (5.0, 10.0),
+                (0.0, 10.0),
+                (0.0, 10.0),
+                (0.0, 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn ping_pong_paths_turn_back_at_either_end() {
+        let platform = Platform::new(square())
+            .with_mode(PathMode::PingPong)
+            .with_speed(5.0);
+        let positions = positions(platform, 14);
+        assert_eq!(positions[5], (0.0, 10.0));
+        assert_eq!(
+            positions[6..],
+            [
+                (5.0, 10.0),
+                (10.0, 10.0),
+                (10.0, 5.0),
+                (10.0, 0.0),
+                (5.0, 0.0),
+                (0.0, 0.0),
+                (5.0, 0.0),
+                (10.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn looping_paths_return_straight_to_the_first_waypoint() {
+        let platform = Platform::new(square())
+            .with_mode(PathMode::Loop)
+            .with_speed(5.0);
+        let positions = positions(platform, 10);
+        assert_eq!(positions[5], (0.0, 10.0));
+        assert_eq!(
+            positions[6..],
+            [(0.0, 5.0), (0.0, 0.0), (5.0, 0.0), (10.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn prefab_waypoints_are_offsets_from_the_spawn() {
+        let mut world = testing::empty_world(Map::default());
+        let platform = world.spawn("platform", 300.0, 600.0).unwrap();
+        run(&mut world, 5);
+
+        let rect = world.entity_manager.get_object(platform).unwrap().rect;
+        assert_eq!((rect.x, rect.y), (350.0, 600.0));
+    }
+}
@@ -0,0 +1,343 @@
+use nalgebra::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+
+use crate::ai::{Chase, Jumper, Patrol};
+use crate::entity::EntityBuilder;
+use crate::error::Error;
+use crate::input::{Input, PlayerInput};
+use crate::layers::{LayerMask, Layers};
+use crate::map::Spawn;
+use crate::object::{Collision, Movement, Object, Shape};
+use crate::platform::{PathMode, Platform};
+use crate::trigger::Trigger;
+
+/// An `Object` as described by a prefab, which is given its position when spawned.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectPrefab {
+    pub width: f32,
+    pub height: f32,
+    #[serde(default = "ObjectPrefab::default_collision")]
+    pub collision: Collision,
+    #[serde(default)]
+    pub shape: Shape,
+    #[serde(default)]
+    pub layer: LayerMask,
+    #[serde(default = "LayerMask::all")]
+    pub mask: LayerMask,
+}
+
+impl ObjectPrefab {
+    fn default_collision() -> Collision {
+        Collision::Solid
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MovementPrefab {
+    pub max_speed: (Option<f32>, Option<f32>),
+    /// The constant forces acting on the entity, such as gravity, as `[x, y]`.
+    pub forces: Vec<[f32; 2]>,
+}
+
+/// A `Platform` as described by a prefab, with its waypoints given as `[x, y]` offsets from
+/// where it's spawned.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformPrefab {
+    pub waypoints: Vec<[f32; 2]>,
+    #[serde(default = "PlatformPrefab::default_mode")]
+    pub mode: PathMode,
+    #[serde(default = "PlatformPrefab::default_speed")]
+    pub speed: f32,
+}
+
+impl PlatformPrefab {
+    fn default_mode() -> PathMode {
+        PathMode::PingPong
+    }
+
+    fn default_speed() -> f32 {
+        1.0
+    }
+}
+
+/// A `Trigger` as described by a prefab.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TriggerPrefab {
+    /// Only entities with this tag are reported, if it's set.
+    pub tag: Option<String>,
+}
+
+/// Which `Input` a prefab's entities are controlled by.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum InputKind {
+    Player,
+    Patrol,
+    /// Chases the nearest entity tagged with `target` once it's within `radius`.
+    Chase {
+        radius: f32,
+        #[serde(default = "InputKind::default_target")]
+        target: String,
+    },
+    /// Jumps every `interval` ticks.
+    Jumper {
+        interval: u32,
+    },
+    None,
+}
+
+impl InputKind {
+    fn default_target() -> String {
+        "player".to_string()
+    }
+}
+
+/// The components making up a type of entity, as read from `assets/prefabs.json`. Unknown
+/// fields are refused, so that a misspelt field isn't silently left at its default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Prefab {
+    pub asset: Option<String>,
+    pub tags: Vec<String>,
+    pub object: Option<ObjectPrefab>,
+    pub movement: Option<MovementPrefab>,
+    pub input: Option<InputKind>,
+    pub platform: Option<PlatformPrefab>,
+    pub trigger: Option<TriggerPrefab>,
+}
+
+impl Prefab {
+    /// A builder for an entity made from this prefab, with its object placed at the position.
+    pub fn builder<'a>(&self, x: f32, y: f32) -> EntityBuilder<'a> {
+        let mut builder = EntityBuilder::new();
+
+        if let Some(asset) = &self.asset {
+            builder = builder.with_asset(asset.clone());
+        }
+
+        for tag in self.tags.iter() {
+            builder = builder.with_tag(tag.clone());
+        }
+
+        if let Some(object) = &self.object {
+            builder = builder.with_object(
+                Object::with_size(object.width, object.height)
+                    .collision(object.collision)
+                    .shape(object.shape)
+                    .layers(object.layer.clone(), object.mask.clone())
+                    .at(x, y),
+            );
+        }
+
+        if let Some(movement) = &self.movement {
+            builder = builder.with_movement(movement.forces.iter().fold(
+                Movement::new().with_max_speed(movement.max_speed),
+                |result, force| result.with_force(Vector2::new(force[0], force[1])),
+            ));
+        }
+
+        if let Some(platform) = &self.platform {
+            let waypoints = platform
+                .waypoints
+                .iter()
+                .map(|offset| Point2::new(x + offset[0], y + offset[1]))
+                .collect();
+            builder = builder.with_platform(
+                Platform::new(waypoints)
+                    .with_mode(platform.mode)
+                    .with_speed(platform.speed),
+            );
+        }
+
+        if let Some(trigger) = &self.trigger {
+            builder = builder.with_trigger(match &trigger.tag {
+                Some(tag) => Trigger::new().with_tag(tag.clone()),
+                None => Trigger::new(),
+            });
+        }
+
+        match &self.input {
+            Some(InputKind::Player) => builder.with_input(Input::Player(PlayerInput::new())),
+            Some(InputKind::Patrol) => builder.with_input(Input::Patrol(Patrol::new())),
+            Some(InputKind::Chase { radius, target }) => {
+                builder.with_input(Input::Chase(Chase::new(*radius, target.clone())))
+            }
+            Some(InputKind::Jumper { interval }) => {
+                builder.with_input(Input::Jumper(Jumper::new(*interval)))
+            }
+            Some(InputKind::None) => builder.with_input(Input::None),
+            None => builder,
+        }
+    }
+
+    /// Turns any layer names into bits, so they're ready to be collided.
+    fn resolve_layers(&mut self, layers: &Layers) -> Result<(), Error> {
+        if let Some(object) = &mut self.object {
+            object.layer = layers.resolve(&object.layer)?;
+            object.mask = layers.resolve(&object.mask)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every prefab, by name.
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+    layers: Layers,
+}
+
+impl Prefabs {
+    pub fn new(prefabs: HashMap<String, Prefab>, layers: Layers) -> Self {
+        Self { prefabs, layers }
+    }
+
+    pub fn load(layers: Layers) -> Result<Self, Error> {
+        let file = File::open("assets/prefabs.json")?;
+        Self::read(io::BufReader::new(file), layers)
+    }
+
+    /// Reads prefabs laid out as in `assets/prefabs.json`, turning their layer names into bits.
+    pub fn read<R: Read>(reader: R, layers: Layers) -> Result<Self, Error> {
+        let mut prefabs: HashMap<String, Prefab> = serde_json::from_reader(reader)?;
+        for prefab in prefabs.values_mut() {
+            prefab.resolve_layers(&layers)?;
+        }
+        Ok(Self::new(prefabs, layers))
+    }
+
+    /// The named layers, which the prefabs' layer names were resolved with.
+    pub fn get_layers(&self) -> &Layers {
+        &self.layers
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn builder<'a>(&self, name: &str, x: f32, y: f32) -> Result<EntityBuilder<'a>, Error> {
+        self.get(name)
+            .map(|prefab| prefab.builder(x, y))
+            .ok_or_else(|| Error::UnknownPrefab(name.to_string()))
+    }
+
+    /// The prefab a spawn is made from, with the spawn's overrides merged over it.
+    pub fn resolve(&self, spawn: &Spawn) -> Result<Prefab, Error> {
+        match &spawn.overrides {
+            Some(overrides) => self.with_overrides(&spawn.prefab, overrides),
+            None => self
+                .get(&spawn.prefab)
+                .cloned()
+                .ok_or_else(|| Error::UnknownPrefab(spawn.prefab.clone())),
+        }
+    }
+
+    /// The named prefab with the overrides merged over it. Objects in the overrides are merged
+    /// field by field, while anything else replaces the prefab's value outright.
+    pub fn with_overrides(&self, name: &str, overrides: &Value) -> Result<Prefab, Error> {
+        let prefab = self
+            .get(name)
+            .ok_or_else(|| Error::UnknownPrefab(name.to_string()))?;
+
+        let mut value = serde_json::to_value(prefab)?;
+        merge(&mut value, overrides);
+
+        let mut prefab: Prefab = serde_json::from_value(value)?;
+        prefab.resolve_layers(&self.layers)?;
+        Ok(prefab)
+    }
+}
+
+fn merge(target: &mut Value, overrides: &Value) {
+    match (target, overrides) {
+        (Value::Object(target), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (target, overrides) => *target = overrides.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityManager;
+    use crate::rect::Rect;
+    use crate::testing;
+    use serde_json::json;
+
+    #[test]
+    fn unknown_prefabs_are_errors() {
+        match testing::prefabs().builder("dragon", 0.0, 0.0) {
+            Err(Error::UnknownPrefab(name)) => assert_eq!(name, "dragon"),
+            Err(other) => panic!("expected an unknown prefab error, got {:?}", other),
+            Ok(_) => panic!("expected an unknown prefab error"),
+        }
+    }
+
+    #[test]
+    fn overrides_only_replace_the_fields_they_give() {
+        let walker = testing::prefabs()
+            .with_overrides(
+                "walker",
+                &json!({ "movement": { "max_speed": [5.0, null] } }),
+            )
+            .unwrap();
+
+        let movement = walker.movement.unwrap();
+        assert_eq!(movement.max_speed, (Some(5.0), None));
+        assert_eq!(movement.forces, vec![[0.0, 0.5]]);
+        let object = walker.object.unwrap();
+        assert_eq!((object.width, object.height), (60.0, 60.0));
+        assert_eq!(object.layer, LayerMask::Bits(0b100));
+        assert_eq!(walker.input, Some(InputKind::Patrol));
+    }
+
+    #[test]
+    fn bad_overrides_are_errors() {
+        let prefabs = testing::prefabs();
+        let mistyped = json!({ "object": { "width": "wide" } });
+        let misspelt = json!({ "movement": { "max_sped": [5.0, null] } });
+        let unknown_layer = json!({ "object": { "layer": ["ghost"] } });
+
+        match prefabs.with_overrides("walker", &mistyped) {
+            Err(Error::SerdeError(_)) => (),
+            other => panic!("expected a serde error, got {:?}", other),
+        }
+        match prefabs.with_overrides("walker", &misspelt) {
+            Err(Error::SerdeError(_)) => (),
+            other => panic!("expected a serde error, got {:?}", other),
+        }
+        match prefabs.with_overrides("walker", &unknown_layer) {
+            Err(Error::UnknownLayer(layer)) => assert_eq!(layer, "ghost"),
+            other => panic!("expected an unknown layer error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prefabs_build_entities_with_their_components() {
+        let mut entity_manager = EntityManager::new();
+        let walker =
+            entity_manager.add(testing::prefabs().builder("walker", 100.0, 200.0).unwrap());
+
+        let object = entity_manager.get_object(walker).unwrap();
+        assert_eq!(object.rect, Rect::new(100.0, 200.0, 60.0, 60.0));
+        assert!(!object.collides_with(&object.clone()));
+
+        let movement = entity_manager.get::<Movement>(walker).unwrap();
+        assert_eq!(movement.get_max_speed(), (Some(10.0), Some(20.0)));
+        assert_eq!(movement.get_constant_force(), Vector2::new(0.0, 0.5));
+        match entity_manager.get_input(walker) {
+            Input::Patrol(_) => (),
+            other => panic!("expected a patrol, got {:?}", other),
+        }
+        assert!(entity_manager.has_tag(walker, "enemy"));
+    }
+}
@@ -0,0 +1,411 @@
+use nalgebra::Point2;
+use std::collections::{BTreeSet, VecDeque};
+
+use crate::acceleration::Acceleration;
+use crate::error::Error;
+use crate::input::JUMP_FORCE;
+use crate::map::{Map, Spawn};
+use crate::navigation::Tile;
+use crate::object::Collision;
+use crate::prefab::{Prefab, Prefabs};
+use crate::rect::Rect;
+
+/// The tag given to the prefabs of a level's exits.
+pub const EXIT_TAG: &str = "exit";
+/// The tag given to the prefabs of things the player can pick up.
+pub const COLLECTIBLE_TAG: &str = "collectible";
+
+/// How long an arc is followed for, in ticks, in case it never lands anywhere.
+const MAX_TICKS: u32 = 600;
+
+/// How the player gets around, as far as which parts of a level it can get to goes.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Abilities {
+    pub width: f32,
+    pub height: f32,
+    /// The fastest the player moves sideways.
+    pub speed: f32,
+    /// The fastest the player falls, if there's a limit.
+    pub max_fall: Option<f32>,
+    pub gravity: f32,
+    /// The force of the player's jump, applied for a single tick.
+    pub jump_force: f32,
+}
+
+impl Abilities {
+    /// The abilities of entities made from the prefab, or `None` if they don't have both an
+    /// object and a movement. Without a top speed of their own, they're taken to run as fast
+    /// as the acceleration lets them.
+    pub fn from_prefab(prefab: &Prefab, acceleration: &Acceleration) -> Option<Self> {
+        let object = prefab.object.as_ref()?;
+        let movement = prefab.movement.as_ref()?;
+        let (max_x, max_y) = movement.max_speed;
+
+        Some(Self {
+            width: object.width,
+            height: object.height,
+            speed: max_x.unwrap_or(acceleration.run_speed),
+            max_fall: max_y,
+            gravity: movement.forces.iter().map(|force| force[1]).sum(),
+            jump_force: JUMP_FORCE,
+        })
+    }
+}
+
+/// The tiles the player can get to from a starting point.
+///
+/// These are found by following the arcs of every walk, fall and jump the player can make from
+/// each spot it can stand on, starting from wherever it lands when dropped at the starting
+/// point. Sideways speed is held constant through each arc, so the player is taken to have a
+/// run up for every jump, and like `NavGraph`, slopes are treated as if they were full tiles.
+pub struct Reachability {
+    standing: BTreeSet<Tile>,
+    touched: BTreeSet<Tile>,
+    tilesize: f32,
+}
+
+impl Reachability {
+    pub fn new(map: &Map, abilities: Abilities, start: Point2<f32>) -> Self {
+        let simulation = Simulation { map, abilities };
+        let mut reachability = Self {
+            standing: BTreeSet::new(),
+            touched: BTreeSet::new(),
+            tilesize: map.get_tilesize(),
+        };
+
+        let dropped = Rect::new(start.x, start.y, abilities.width, abilities.height);
+        let mut queue: VecDeque<Tile> = simulation
+            .follow(dropped, 0.0, false, &mut reachability.touched)
+            .into_iter()
+            .collect();
+        reachability.standing.extend(queue.iter().cloned());
+
+        while let Some(spot) = queue.pop_front() {
+            for &(speed, jump) in simulation.arcs().iter() {
+                let rect = simulation.standing_at(spot);
+                for landing in simulation.follow(rect, speed, jump, &mut reachability.touched) {
+                    if reachability.standing.insert(landing) {
+                        queue.push_back(landing);
+                    }
+                }
+            }
+        }
+
+        reachability
+    }
+
+    /// Whether the player can stand with its feet in the tile.
+    pub fn can_stand_in(&self, tile: Tile) -> bool {
+        self.standing.contains(&tile)
+    }
+
+    /// Whether any part of the player can get into the tile.
+    pub fn is_reachable(&self, tile: Tile) -> bool {
+        self.touched.contains(&tile)
+    }
+
+    /// Whether any part of the player can get into any of the tiles the rect covers.
+    pub fn can_reach(&self, rect: &Rect<f32>) -> bool {
+        tiles_in(rect, self.tilesize).any(|tile| {
+            tile.0 >= 0 && tile.1 >= 0 && self.is_reachable((tile.0 as u16, tile.1 as u16))
+        })
+    }
+}
+
+/// The exits and collectibles spawned by the map which the player can't get to from where it
+/// spawns. If the map doesn't spawn a player, none of them can be got to.
+pub fn unreachable_targets(
+    map: &Map,
+    prefabs: &Prefabs,
+    acceleration: &Acceleration,
+) -> Result<Vec<Spawn>, Error> {
+    let mut player = None;
+    let mut targets = Vec::new();
+    for spawn in map.entities.iter() {
+        let prefab = prefabs.resolve(spawn)?;
+        let has_tag = |tag: &str| prefab.tags.iter().any(|other| other == tag);
+
+        if player.is_none() && has_tag("player") {
+            player = Abilities::from_prefab(&prefab, acceleration)
+                .map(|abilities| (abilities, Point2::new(spawn.x, spawn.y)));
+        }
+
+        if has_tag(EXIT_TAG) || has_tag(COLLECTIBLE_TAG) {
+            let rect = match &prefab.object {
+                Some(object) => Rect::new(spawn.x, spawn.y, object.width, object.height),
+                None => Rect::new(spawn.x, spawn.y, 1.0, 1.0),
+            };
+            targets.push((spawn, rect));
+        }
+    }
+
+    let reachability = player.map(|(abilities, start)| Reachability::new(map, abilities, start));
+    Ok(targets
+        .into_iter()
+        .filter(|(_, rect)| {
+            !reachability
+                .as_ref()
+                .map_or(false, |reachability| reachability.can_reach(rect))
+        })
+        .map(|(spawn, _)| spawn.clone())
+        .collect())
+}
+
+/// Moves the player's rect through the map a tick at a time, much like `EntityManager::update`
+/// but against the map alone.
+struct Simulation<'m> {
+    map: &'m Map<'m>,
+    abilities: Abilities,
+}
+
+impl<'m> Simulation<'m> {
+    /// The sideways speeds and whether to jump for each arc followed from a standing spot.
+    fn arcs(&self) -> Vec<(f32, bool)> {
+        let speed = self.abilities.speed;
+        let mut arcs = vec![(0.0, true)];
+        for &direction in &[-1.0, 1.0] {
+            for &speed in &[speed, speed / 2.0] {
+                arcs.push((direction * speed, false));
+                arcs.push((direction * speed, true));
+            }
+        }
+        arcs
+    }
+
+    /// The player's rect when standing in the middle of the tile.
+    fn standing_at(&self, tile: Tile) -> Rect<f32> {
+        let tilesize = self.map.get_tilesize();
+        Rect::new(
+            (tile.0 as f32 + 0.5) * tilesize - self.abilities.width / 2.0,
+            (tile.1 as f32 + 1.0) * tilesize - self.abilities.height,
+            self.abilities.width,
+            self.abilities.height,
+        )
+    }
+
+    /// Follows the player's rect as it moves sideways at the speed, jumping first if asked to,
+    /// until it lands after having left the ground or walks into a wall. Every tile the rect
+    /// passes through is added to `touched`, and the spots it stands in along the way are
+    /// returned.
+    fn follow(
+        &self,
+        mut rect: Rect<f32>,
+        speed: f32,
+        jump: bool,
+        touched: &mut BTreeSet<Tile>,
+    ) -> Vec<Tile> {
+        let tilesize = self.map.get_tilesize();
+        let bottom_of_map = self.map.height as f32 * tilesize;
+        let mut vertical_speed = 0.0;
+        let mut airborne = false;
+        let mut spots: Vec<Tile> = Vec::new();
+
+        for tick in 0..MAX_TICKS {
+            let jump_force = if jump && tick == 0 {
+                self.abilities.jump_force
+            } else {
+                0.0
+            };
+            vertical_speed += jump_force + self.abilities.gravity;
+            if let Some(max_fall) = self.abilities.max_fall {
+                vertical_speed = vertical_speed.signum() * f32::min(vertical_speed.abs(), max_fall);
+            }
+
+            let walled = self.move_x(&mut rect, speed);
+            let landed = self.move_y(&mut rect, &mut vertical_speed);
+            self.touch(&rect, touched);
+
+            if rect.y >= bottom_of_map {
+                break;
+            }
+
+            if landed {
+                let spot = (
+                    ((rect.x + rect.width / 2.0) / tilesize) as u16,
+                    ((rect.y + rect.height - 0.5) / tilesize) as u16,
+                );
+                if spots.last() != Some(&spot) {
+                    spots.push(spot);
+                }
+                if airborne || walled || speed == 0.0 {
+                    break;
+                }
+            } else {
+                airborne = true;
+            }
+        }
+        spots
+    }
+
+    /// Moves the rect sideways, stopping it against any wall. Returns whether it hit one.
+    fn move_x(&self, rect: &mut Rect<f32>, speed: f32) -> bool {
+        let tilesize = self.map.get_tilesize();
+        rect.x += speed;
+        if speed == 0.0 || !self.hits(rect, |_, collision| collision == Collision::Solid) {
+            return false;
+        }
+
+        rect.x = if speed > 0.0 {
+            ((rect.x + rect.width) / tilesize).floor() * tilesize - rect.width
+        } else {
+            ((rect.x / tilesize).floor() + 1.0) * tilesize
+        };
+        true
+    }
+
+    /// Moves the rect up or down, stopping it against any ceiling or floor. Returns whether it
+    /// landed on a floor.
+    fn move_y(&self, rect: &mut Rect<f32>, speed: &mut f32) -> bool {
+        let tilesize = self.map.get_tilesize();
+        let bottom = rect.y + rect.height;
+        rect.y += *speed;
+
+        if *speed > 0.0 {
+            let landed = self.hits(rect, |top, collision| match collision {
+                Collision::Solid => true,
+                Collision::OneWayUp => top >= bottom - 0.01,
+                Collision::None => false,
+            });
+            if landed {
+                rect.y = ((rect.y + rect.height) / tilesize).floor() * tilesize - rect.height;
+                *speed = 0.0;
+            }
+            landed
+        } else {
+            if self.hits(rect, |_, collision| collision == Collision::Solid) {
+                rect.y = ((rect.y / tilesize).floor() + 1.0) * tilesize;
+                *speed = 0.0;
+            }
+            false
+        }
+    }
+
+    /// Whether any of the tiles the rect covers block it, given each tile's top and collision.
+    fn hits<F>(&self, rect: &Rect<f32>, blocks: F) -> bool
+    where
+        F: Fn(f32, Collision) -> bool,
+    {
+        let tilesize = self.map.get_tilesize();
+        tiles_in(rect, tilesize)
+            .any(|(x, y)| blocks(y as f32 * tilesize, self.map.collision_at(x, y)))
+    }
+
+    fn touch(&self, rect: &Rect<f32>, touched: &mut BTreeSet<Tile>) {
+        let (width, height) = (self.map.width as i32, self.map.height as i32);
+        touched.extend(
+            tiles_in(rect, self.map.get_tilesize())
+                .filter(|&(x, y)| x >= 0 && y >= 0 && x < width && y < height)
+                .map(|(x, y)| (x as u16, y as u16)),
+        );
+    }
+}
+
+/// The tiles that the rect overlaps, not counting those it only touches the edge of.
+fn tiles_in(rect: &Rect<f32>, tilesize: f32) -> impl Iterator<Item = (i32, i32)> {
+    let min_x = (rect.x / tilesize).floor() as i32;
+    let max_x = ((rect.x + rect.width) / tilesize).ceil() as i32;
+    let min_y = (rect.y / tilesize).floor() as i32;
+    let max_y = ((rect.y + rect.height) / tilesize).ceil() as i32;
+    (min_y..max_y).flat_map(move |y| (min_x..max_x).map(move |x| (x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn unreachable(ascii: &str) -> Vec<String> {
+        let map = Map::from_ascii(ascii);
+        unreachable_targets(&map, &testing::prefabs(), &Acceleration::default())
+            .unwrap()
+            .into_iter()
+            .map(|spawn| spawn.prefab)
+            .collect()
+    }
+
+    #[test]
+    fn the_player_walks_to_the_exit() {
+        let map = Map::from_ascii(
+            "
+            ..........
+            ..........
+            ..........
+            .@......E.
+            ##########
+            ",
+        );
+        let player = testing::prefabs().get("player").cloned().unwrap();
+        let reachability = Reachability::new(
+            &map,
+            Abilities::from_prefab(&player, &Acceleration::default()).unwrap(),
+            Point2::new(70.0, 210.0),
+        );
+
+        assert!((0..10).all(|x| reachability.can_stand_in((x, 3))));
+        assert!(!reachability.can_stand_in((4, 2)));
+        assert!(
+            unreachable_targets(&map, &testing::prefabs(), &Acceleration::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn high_ledges_are_out_of_reach() {
+        let ascii = "
+            ........E.
+            ........##
+            ....C.....
+            ..........
+            ..........
+            .@........
+            ##########
+            ";
+
+        assert_eq!(unreachable(ascii), vec!["exit"]);
+    }
+
+    #[test]
+    fn only_narrow_enough_gaps_can_be_jumped() {
+        let narrow = "
+            ..............
+            ..............
+            .@..........E.
+            #####....#####
+            ";
+        assert!(unreachable(narrow).is_empty());
+
+        let wide = "
+            ...................
+            ...................
+            .@...............E.
+            #####..........####
+            ";
+        assert_eq!(unreachable(wide), vec!["exit"]);
+    }
+
+    #[test]
+    fn one_way_platforms_are_jumped_up_through() {
+        let ascii = "
+            ....E...
+            ........
+            ........
+            ========
+            ........
+            .@......
+            ########
+            ";
+
+        assert!(unreachable(ascii).is_empty());
+    }
+
+    #[test]
+    fn nothing_is_reachable_without_a_player() {
+        let ascii = "
+            .C....E.
+            ########
+            ";
+
+        assert_eq!(unreachable(ascii), vec!["coin", "exit"]);
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+use std::io::{Read, Write};
+
+use crate::entity::SavedEntities;
+use crate::error::Error;
+use crate::map::Map;
+
+/// The version of the save file layout, bumped whenever it changes so that older saves are
+/// rejected rather than misread.
+pub const SAVE_VERSION: u64 = 1;
+
+pub const SAVE_PATH: &str = "save.json";
+
+/// Everything needed to pick a game back up: the map, and every entity in it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SaveGame<'a> {
+    version: u64,
+    pub map: Map<'a>,
+    pub entities: SavedEntities<'a>,
+}
+
+impl<'a> SaveGame<'a> {
+    pub fn new(map: Map<'a>, entities: SavedEntities<'a>) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            map,
+            entities,
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Error> {
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<Self, Error> {
+        let save: Value = serde_json::from_reader(reader)?;
+        let version = save.get("version").and_then(Value::as_u64).unwrap_or(0);
+        if version != SAVE_VERSION {
+            return Err(Error::UnsupportedSaveVersion(version));
+        }
+        Ok(serde_json::from_value(save)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityBuilder;
+    use crate::layers::LayerMask;
+    use crate::object::{Collision, Movement, Object};
+    use crate::platform::Platform;
+    use crate::testing::{self, run};
+    use crate::trigger::Trigger;
+    use crate::world::World;
+    use nalgebra::{Point2, Vector2};
+
+    fn world() -> World<'static> {
+        let mut world = testing::world(Map::default());
+
+        world.entity_manager.add(
+            EntityBuilder::new()
+                .with_object(Object::with_size(140.0, 35.0).at(300.0, 600.0))
+                .with_platform(Platform::new(vec![
+                    Point2::new(300.0, 600.0),
+                    Point2::new(700.0, 600.0),
+                ])),
+        );
+        world.entity_manager.add(
+            EntityBuilder::new()
+                .with_object(
+                    Object::with_size(70.0, 700.0)
+                        .at(140.0, 0.0)
+                        .collision(Collision::None),
+                )
+                .with_trigger(Trigger::new()),
+        );
+
+        let player = world.entity_manager.by_name("player").unwrap();
+        world
+            .entity_manager
+            .get_movement_mut(player)
+            .unwrap()
+            .add_instantaneous_force(Vector2::new(8.0, -10.0));
+        world
+    }
+
+    fn snapshot(world: &World) -> Value {
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+        serde_json::from_slice(&save).unwrap()
+    }
+
+    fn reload(world: &World) -> World<'static> {
+        let mut save = Vec::new();
+        world.save(&mut save).unwrap();
+
+        let mut reloaded = testing::empty_world(Map::default());
+        reloaded.restore(save.as_slice()).unwrap();
+        reloaded
+    }
+
+    #[test]
+    fn round_trip_preserves_the_world() {
+        let mut world = world();
+        run(&mut world, 5);
+
+        assert_eq!(snapshot(&world), snapshot(&reload(&world)));
+    }
+
+    #[test]
+    fn simulation_continues_identically_after_loading() {
+        let mut world = world();
+        run(&mut world, 10);
+
+        let mut reloaded = reload(&world);
+        run(&mut world, 60);
+        run(&mut reloaded, 60);
+
+        let player = world.entity_manager.by_name("player").unwrap();
+        assert_eq!(
+            world.entity_manager.get_object(player).unwrap().rect,
+            reloaded.entity_manager.get_object(player).unwrap().rect,
+        );
+        assert_eq!(snapshot(&world), snapshot(&reloaded));
+    }
+
+    #[test]
+    fn entity_handles_survive_loading() {
+        let mut world = world();
+        let doomed = world
+            .entity_manager
+            .add(EntityBuilder::new().with_object(Object::with_size(10.0, 10.0).at(500.0, 500.0)));
+        world.entity_manager.despawn(doomed);
+
+        let mut reloaded = reload(&world);
+        assert!(!reloaded.entity_manager.is_alive(doomed));
+
+        let spawned = world.spawn("player", 200.0, 200.0).unwrap();
+        let respawned = reloaded.spawn("player", 200.0, 200.0).unwrap();
+        assert_eq!(spawned, respawned);
+        assert_ne!(spawned, doomed);
+    }
+
+    #[test]
+    fn named_layers_are_resolved_when_loading() {
+        let mut world = world();
+        let names =
+            |names: &[&str]| LayerMask::Names(names.iter().map(|name| name.to_string()).collect());
+        let mut object = Object::with_size(60.0, 60.0)
+            .at(700.0, 920.0)
+            .layers(names(&["enemy"]), names(&["default", "player"]));
+        object.resolve_layers(world.prefabs.get_layers()).unwrap();
+        let enemy = world.entity_manager.add(
+            EntityBuilder::new()
+                .with_object(object)
+                .with_movement(Movement::new().with_force(Vector2::new(0.0, 0.5))),
+        );
+
+        let mut reloaded = reload(&world);
+        run(&mut reloaded, 30);
+
+        // Still standing on the floor, rather than falling through everything.
+        let object = reloaded.entity_manager.get_object(enemy).unwrap();
+        assert_eq!(object.rect.y, 920.0);
+        assert!(object.collides_with(&Object::with_size(70.0, 70.0)));
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut save = snapshot(&world());
+        save["version"] = Value::from(SAVE_VERSION + 1);
+
+        match SaveGame::read(save.to_string().as_bytes()) {
+            Err(Error::UnsupportedSaveVersion(version)) => assert_eq!(version, SAVE_VERSION + 1),
+            other => panic!("expected a version error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+
+/// How a tile affects the entities standing on it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+#[serde(default)]
+pub struct Surface {
+    /// How much horizontal speed is lost each tick.
+    pub friction: f32,
+    /// The fraction of an entity's falling speed that it bounces back up with.
+    pub restitution: f32,
+    /// The horizontal speed at which entities are carried along.
+    pub conveyor: f32,
+    /// The damage dealt each tick to entities standing on it.
+    pub damage: f32,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {
+            friction: 0.4,
+            restitution: 0.0,
+            conveyor: 0.0,
+            damage: 0.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SurfaceEntry {
+    name: String,
+    #[serde(default)]
+    surface: Option<Surface>,
+}
+
+#[derive(Deserialize)]
+struct SurfaceAtlas {
+    items: Vec<SurfaceEntry>,
+}
+
+/// The surface properties declared by each tile in the texture atlas.
+pub struct Surfaces<'a> {
+    surfaces: HashMap<Cow<'a, str>, Surface>,
+}
+
+impl<'a> Surfaces<'a> {
+    pub fn new(surfaces: HashMap<Cow<'a, str>, Surface>) -> Self {
+        Self { surfaces }
+    }
+
+    /// Reads the surfaces out of the texture atlas, without loading anything needed only for
+    /// drawing it.
+    pub fn load() -> Result<Self, Error> {
+        let file = File::open("assets/tiles.json")?;
+        let reader = io::BufReader::new(file);
+        let atlas: SurfaceAtlas = serde_json::from_reader(reader)?;
+        Ok(Self::new(
+            atlas
+                .items
+                .into_iter()
+                .filter_map(|entry| {
+                    entry
+                        .surface
+                        .map(|surface| (Cow::Owned(entry.name), surface))
+                })
+                .collect(),
+        ))
+    }
+
+    pub fn get(&self, asset_name: Option<&str>) -> Surface {
+        asset_name
+            .and_then(|name| self.surfaces.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{Entity, EntityBuilder};
+    use crate::event::Damaged;
+    use crate::map::Map;
+    use crate::object::{Movement, Object};
+    use crate::testing::{self, run};
+    use crate::world::World;
+    use nalgebra::Vector2;
+
+    /// A world whose walls and floor, made of `box` tiles, have the surface.
+    fn world_on(surface: Surface) -> World<'static> {
+        let mut world = testing::empty_world(Map::default());
+        let mut surfaces = HashMap::new();
+        surfaces.insert(Cow::Borrowed("box"), surface);
+        world.surfaces = Surfaces::new(surfaces);
+        world
+    }
+
+    fn falling_box(world: &mut World, x: f32, y: f32) -> Entity {
+        world.entity_manager.add(
+            EntityBuilder::new()
+                .with_object(Object::with_size(60.0, 60.0).at(x, y))
+                .with_movement(
+                    Movement::new()
+                        .with_max_speed((Some(10.0), Some(20.0)))
+                        .with_force(Vector2::new(0.0, 0.5)),
+                ),
+        )
+    }
+
+    fn x(world: &World, entity: Entity) -> f32 {
+        world.entity_manager.get_object(entity).unwrap().rect.x
+    }
+
+    /// How far a box pushed along the floor slides in a second.
+    fn slide(surface: Surface) -> f32 {
+        let mut world = world_on(surface);
+        let entity = falling_box(&mut world, 700.0, 920.0);
+        run(&mut world, 1);
+
+        world
+            .entity_manager
+            .get_movement_mut(entity)
+            .unwrap()
+            .add_instantaneous_force(Vector2::new(8.0, 0.0));
+        run(&mut world, 60);
+        x(&world, entity) - 700.0
+    }
+
+    #[test]
+    fn entities_slide_further_on_ice() {
+        let ground = slide(Surface::default());
+        let ice = slide(Surface {
+            friction: 0.05,
+            ..Surface::default()
+        });
+
+        assert!(ground > 0.0);
+        assert!(ice > ground * 2.0);
+    }
+
+    #[test]
+    fn bounces_lose_height_each_time() {
+        let mut world = world_on(Surface {
+            restitution: 0.5,
+            ..Surface::default()
+        });
+        let entity = falling_box(&mut world, 700.0, 500.0);
+
+        // The highest point reached before each landing.
+        let mut peaks = Vec::new();
+        let mut peak = 500.0;
+        for _ in 0..300 {
+            run(&mut world, 1);
+            let y = world.entity_manager.get_object(entity).unwrap().rect.y;
+            peak = f32::min(peak, y);
+            if world
+                .entity_manager
+                .get::<Movement>(entity)
+                .unwrap()
+                .is_grounded()
+            {
+                peaks.push(peak);
+                peak = y;
+            }
+        }
+
+        assert!(peaks.len() >= 3);
+        assert!(peaks[1] < 920.0);
+        assert!(peaks[0] < peaks[1] && peaks[1] < peaks[2]);
+    }
+
+    #[test]
+    fn conveyors_carry_entities_along() {
+        let mut world = world_on(Surface {
+            conveyor: 2.0,
+            ..Surface::default()
+        });
+        let entity = falling_box(&mut world, 700.0, 920.0);
+        run(&mut world, 1);
+
+        run(&mut world, 30);
+        assert_eq!(x(&world, entity), 700.0 + 2.0 * 30.0);
+        let movement = world.entity_manager.get::<Movement>(entity).unwrap();
+        assert_eq!(movement.get_speed().x, 0.0);
+    }
+
+    #[test]
+    fn damaging_surfaces_hurt_whatever_stands_on_them() {
+        let mut world = world_on(Surface {
+            damage: 1.0,
+            ..Surface::default()
+        });
+        let standing = falling_box(&mut world, 700.0, 920.0);
+        falling_box(&mut world, 1000.0, 100.0);
+        run(&mut world, 2);
+
+        let damaged: Vec<&Damaged> = world.events.read::<Damaged>().collect();
+        assert_eq!(
+            damaged,
+            vec![&Damaged {
+                entity: standing,
+                amount: 1.0
+            }]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use crate::event::Damaged;
+use crate::input::Input;
+use crate::object::Movement;
+use crate::trigger::{TriggerEvent, TriggerEventKind};
+use crate::world::World;
+use std::collections::BTreeMap;
+
+/// The phases of a tick, run in the order they're declared.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Stage {
+    Input,
+    Ai,
+    Physics,
+    Triggers,
+    Cleanup,
+}
+
+/// A piece of per-tick game logic.
+pub trait System<'a> {
+    /// Changes the world. Systems always run one at a time, in the order they were added.
+    fn run(&mut self, world: &mut World<'a>);
+}
+
+impl<'a, F> System<'a> for F
+where
+    F: FnMut(&mut World<'a>),
+{
+    fn run(&mut self, world: &mut World<'a>) {
+        self(world)
+    }
+}
+
+/// The systems making up a tick, grouped by the stage they run in.
+pub struct Scheduler<'a> {
+    stages: BTreeMap<Stage, Vec<Box<dyn System<'a> + 'a>>>,
+}
+
+impl<'a> Scheduler<'a> {
+    /// A scheduler without any systems.
+    pub fn new() -> Self {
+        Self {
+            stages: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_system<S>(mut self, stage: Stage, system: S) -> Self
+    where
+        S: System<'a> + 'a,
+    {
+        self.add_system(stage, system);
+        self
+    }
+
+    pub fn add_system<S>(&mut self, stage: Stage, system: S)
+    where
+        S: System<'a> + 'a,
+    {
+        self.stages
+            .entry(stage)
+            .or_insert_with(Vec::new)
+            .push(Box::new(system));
+    }
+
+    /// Runs a single tick, stage by stage, then makes the events published during it readable.
+    pub fn run(&mut self, world: &mut World<'a>) {
+        for systems in self.stages.values_mut() {
+            for system in systems.iter_mut() {
+                system.run(world);
+            }
+        }
+
+        world.events.advance();
+    }
+}
+
+impl<'a> Default for Scheduler<'a> {
+    /// A scheduler with the built-in systems that every game needs.
+    fn default() -> Self {
+        Self::new()
+            .with_system(Stage::Input, InputForces)
+            .with_system(Stage::Ai, Ai)
+            .with_system(Stage::Physics, Physics)
+            .with_system(Stage::Physics, Hierarchy)
+            .with_system(Stage::Triggers, Triggers)
+            .with_system(Stage::Triggers, Collect)
+            .with_system(Stage::Cleanup, Cleanup)
+    }
+}
+
+/// Steers each entity's movement in the direction its input asks for, and applies any force
+/// it asks for, such as a jump.
+pub struct InputForces;
+
+impl<'a> System<'a> for InputForces {
+    fn run(&mut self, world: &mut World<'a>) {
+        let acceleration = world.acceleration;
+        for (_, (input, movement)) in world.entity_manager.query::<(&Input, &mut Movement)>() {
+            if let Some(direction) = input.get_direction() {
+                acceleration.drive(movement, direction, input.is_running());
+            }
+            movement.add_instantaneous_force(input.get_force());
+        }
+    }
+}
+
+/// Lets AI controlled entities decide where to go. Like the player's, their decisions are
+/// turned into forces by `InputForces` at the start of the next tick.
+pub struct Ai;
+
+impl<'a> System<'a> for Ai {
+    fn run(&mut self, world: &mut World<'a>) {
+        let World {
+            map,
+            entity_manager,
+            navigation,
+            acceleration,
+            ..
+        } = world;
+        entity_manager.update_ai(map, navigation, acceleration);
+    }
+}
+
+/// Moves the platforms, then every entity, then separates the entities overlapping each other.
+/// Entities standing on a surface which deals damage are reported.
+pub struct Physics;
+
+impl<'a> System<'a> for Physics {
+    fn run(&mut self, world: &mut World<'a>) {
+        let World {
+            map,
+            entity_manager,
+            surfaces,
+            events,
+            ..
+        } = world;
+
+        entity_manager.update_platforms(map);
+        let platforms = entity_manager.platforms();
+        for entity in entity_manager.get_entities() {
+            entity_manager.update(entity, map, surfaces, &platforms);
+        }
+        entity_manager.resolve_entity_collisions(map);
+
+        for (entity, movement) in entity_manager.iter::<Movement>() {
+            if let Some(surface) = movement.get_surface() {
+                if surface.damage > 0.0 {
+                    events.publish(Damaged {
+                        entity: *entity,
+                        amount: surface.damage,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Moves the children's objects along with their parents', once the parents have moved.
+pub struct Hierarchy;
+
+impl<'a> System<'a> for Hierarchy {
+    fn run(&mut self, world: &mut World<'a>) {
+        world.entity_manager.update_hierarchy();
+    }
+}
+
+/// Reports the entities entering, staying in, and leaving each trigger.
+pub struct Triggers;
+
+impl<'a> System<'a> for Triggers {
+    fn run(&mut self, world: &mut World<'a>) {
+        world.entity_manager.update_triggers();
+        for event in world.entity_manager.drain_trigger_events() {
+            world.events.publish(event);
+        }
+    }
+}
+
+/// Despawns the collectibles which were entered during the previous tick. They lose their
+/// tag straight away, so they no longer count as collectibles while they wait to be despawned.
+pub struct Collect;
+
+impl<'a> System<'a> for Collect {
+    fn run(&mut self, world: &mut World<'a>) {
+        let World {
+            entity_manager,
+            events,
+            ..
+        } = world;
+
+        for event in events.read::<TriggerEvent>() {
+            if event.kind == TriggerEventKind::Enter
+                && entity_manager.remove_tag(event.trigger, "collectible")
+            {
+                entity_manager.queue_despawn(event.trigger);
+            }
+        }
+    }
+}
+
+/// Despawns the entities queued for despawning during the tick, then publishes the spawns and
+/// despawns that happened during it.
+pub struct Cleanup;
+
+impl<'a> System<'a> for Cleanup {
+    fn run(&mut self, world: &mut World<'a>) {
+        world.entity_manager.flush_despawns();
+        for event in world.entity_manager.drain_entity_events() {
+            world.events.publish(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityEvent;
+    use crate::map::Map;
+    use crate::testing::{self, run};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    fn recorder(log: &Log, name: &'static str) -> impl FnMut(&mut World<'static>) {
+        let log = Rc::clone(log);
+        move |_| log.borrow_mut().push(name)
+    }
+
+    #[test]
+    fn stages_run_in_order_and_systems_in_the_order_they_were_added() {
+        let log = Log::default();
+        let mut scheduler = Scheduler::new()
+            .with_system(Stage::Cleanup, recorder(&log, "cleanup"))
+            .with_system(Stage::Triggers, recorder(&log, "triggers"))
+            .with_system(Stage::Physics, recorder(&log, "physics"))
+            .with_system(Stage::Ai, recorder(&log, "ai"))
+            .with_system(Stage::Physics, recorder(&log, "more physics"))
+            .with_system(Stage::Input, recorder(&log, "input"));
+
+        let mut world = testing::empty_world(Map::default());
+        scheduler.run(&mut world);
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "input",
+                "ai",
+                "physics",
+                "more physics",
+                "triggers",
+                "cleanup"
+            ]
+        );
+        assert_eq!(world.events.tick(), 1);
+    }
+
+    /// Spawns a coin each tick.
+    struct Minter;
+
+    impl<'a> System<'a> for Minter {
+        fn run(&mut self, world: &mut World<'a>) {
+            world.spawn("coin", 100.0, 100.0).unwrap();
+        }
+    }
+
+    #[test]
+    fn custom_systems_run_alongside_the_built_in_ones() {
+        let mut scheduler = Scheduler::default();
+        scheduler.add_system(Stage::Input, Minter);
+
+        let mut world = testing::empty_world(Map::default());
+        for _ in 0..3 {
+            scheduler.run(&mut world);
+        }
+
+        assert_eq!(world.entity_manager.tagged("collectible").count(), 3);
+        // The spawn during the last tick was published by the built-in cleanup.
+        assert_eq!(world.events.read::<EntityEvent>().count(), 1);
+    }
+
+    #[test]
+    fn collectibles_are_despawned_once_entered() {
+        let mut world = testing::empty_world(Map::default());
+        world.spawn("player", 700.0, 980.0 - 106.0).unwrap();
+        let coin = world.spawn("coin", 700.0, 910.0).unwrap();
+        let exit = world.spawn("exit", 730.0, 910.0).unwrap();
+
+        run(&mut world, 1);
+        assert!(world.entity_manager.is_alive(coin));
+
+        run(&mut world, 1);
+        assert!(!world.entity_manager.is_alive(coin));
+        assert!(world.entity_manager.is_alive(exit));
+        assert_eq!(
+            world.events.read::<EntityEvent>().collect::<Vec<_>>(),
+            vec![&EntityEvent::Despawned(coin)]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use crate::input_source::{InputSource, Scripted};
+use crate::layers::Layers;
+use crate::map::Map;
+use crate::prefab::Prefabs;
+use crate::surface::Surfaces;
+use crate::system::Scheduler;
+use crate::world::World;
+
+/// The prefabs tests spawn, kept here so that editing `assets/prefabs.json` doesn't change them.
+const PREFABS: &str = r#"{
+    "player": {
+        "tags": ["player"],
+        "object": { "width": 48.0, "height": 106.0 },
+        "movement": { "max_speed": [10.0, 20.0], "forces": [[0.0, 0.5]] },
+        "input": "Player"
+    },
+    "walker": {
+        "tags": ["enemy"],
+        "object": {
+            "width": 60.0,
+            "height": 60.0,
+            "layer": ["enemy"],
+            "mask": ["default", "player"]
+        },
+        "movement": { "max_speed": [10.0, 20.0], "forces": [[0.0, 0.5]] },
+        "input": "Patrol"
+    },
+    "chaser": {
+        "tags": ["enemy"],
+        "object": {
+            "width": 60.0,
+            "height": 60.0,
+            "layer": ["enemy"],
+            "mask": ["default", "player"]
+        },
+        "movement": { "max_speed": [10.0, 20.0], "forces": [[0.0, 0.5]] },
+        "input": { "Chase": { "radius": 420.0 } }
+    },
+    "hopper": {
+        "tags": ["enemy"],
+        "object": {
+            "width": 60.0,
+            "height": 60.0,
+            "layer": ["enemy"],
+            "mask": ["default", "player"]
+        },
+        "movement": { "max_speed": [10.0, 20.0], "forces": [[0.0, 0.5]] },
+        "input": { "Jumper": { "interval": 90 } }
+    },
+    "exit": {
+        "tags": ["exit"],
+        "object": { "width": 70.0, "height": 70.0, "collision": "None" },
+        "trigger": { "tag": "player" }
+    },
+    "coin": {
+        "tags": ["collectible"],
+        "object": { "width": 70.0, "height": 70.0, "collision": "None" },
+        "trigger": { "tag": "player" }
+    },
+    "platform": {
+        "object": { "width": 140.0, "height": 35.0 },
+        "platform": { "waypoints": [[0.0, 0.0], [200.0, 0.0]], "mode": "Loop", "speed": 10.0 }
+    }
+}"#;
+
+pub fn prefabs() -> Prefabs {
+    let layers = Layers::new(
+        ["default", "player", "enemy"]
+            .iter()
+            .map(|layer| layer.to_string())
+            .collect(),
+    );
+    Prefabs::read(PREFABS.as_bytes(), layers).unwrap()
+}
+
+/// A world on the map with no surfaces, and none of the map's entities spawned yet.
+pub fn empty_world(map: Map<'static>) -> World<'static> {
+    World::new(map, Surfaces::new(HashMap::new()), prefabs())
+}
+
+/// A world on the map with no surfaces, and the map's entities spawned.
+pub fn world(map: Map<'static>) -> World<'static> {
+    let mut world = empty_world(map);
+    world.spawn_map_entities().unwrap();
+    world
+}
+
+/// Runs every system for a number of ticks, with nothing held.
+pub fn run(world: &mut World<'static>, ticks: u32) {
+    run_with(world, &mut Scripted::new(), ticks);
+}
+
+/// Runs every system for a number of ticks, with the actions from the input source.
+pub fn run_with(world: &mut World<'static>, input: &mut dyn InputSource, ticks: u32) {
+    let mut scheduler = Scheduler::default();
+    for _ in 0..ticks {
+        let actions = input.next();
+        world.update_inputs(&actions);
+        scheduler.run(world);
+    }
+}
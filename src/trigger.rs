@@ -0,0 +1,159 @@
+use crate::entity::Entity;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TriggerEventKind {
+    /// The entity started overlapping the trigger this tick.
+    Enter,
+    /// The entity was already overlapping the trigger and still is.
+    Stay,
+    /// The entity stopped overlapping the trigger this tick.
+    Exit,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TriggerEvent {
+    pub kind: TriggerEventKind,
+    pub trigger: Entity,
+    pub entity: Entity,
+}
+
+/// Marks an entity's `Object` as a trigger volume, which reports the entities overlapping it
+/// rather than blocking them.
+///
+/// A trigger's object is usually given `Collision::None`, so that nothing collides with it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Trigger {
+    overlapping: BTreeSet<Entity>,
+    /// Only entities with this tag are reported, if it's set.
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+impl Trigger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tag<S>(mut self, tag: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn get_tag(&self) -> Option<&str> {
+        self.tag.as_ref().map(String::as_str)
+    }
+
+    /// Records the entities overlapping the trigger this tick, returning the events for
+    /// everything that entered, stayed in, or exited it.
+    pub fn update(&mut self, trigger: Entity, overlapping: BTreeSet<Entity>) -> Vec<TriggerEvent> {
+        let mut events = Vec::new();
+        for entity in overlapping.iter() {
+            let kind = if self.overlapping.contains(entity) {
+                TriggerEventKind::Stay
+            } else {
+                TriggerEventKind::Enter
+            };
+            events.push(TriggerEvent {
+                kind,
+                trigger,
+                entity: *entity,
+            });
+        }
+
+        for entity in self.overlapping.difference(&overlapping) {
+            events.push(TriggerEvent {
+                kind: TriggerEventKind::Exit,
+                trigger,
+                entity: *entity,
+            });
+        }
+
+        self.overlapping = overlapping;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{EntityBuilder, EntityManager};
+    use crate::map::Map;
+    use crate::testing::{self, run};
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut entity_manager = EntityManager::new();
+        (0..count)
+            .map(|_| entity_manager.add(EntityBuilder::new()))
+            .collect()
+    }
+
+    fn kinds(events: Vec<TriggerEvent>) -> Vec<(TriggerEventKind, Entity)> {
+        events
+            .into_iter()
+            .map(|event| (event.kind, event.entity))
+            .collect()
+    }
+
+    #[test]
+    fn entities_enter_stay_in_and_exit_triggers() {
+        let entities = entities(3);
+        let (volume, a, b) = (entities[0], entities[1], entities[2]);
+        let mut trigger = Trigger::new();
+
+        let events = trigger.update(volume, vec![a].into_iter().collect());
+        assert!(events.iter().all(|event| event.trigger == volume));
+        assert_eq!(kinds(events), vec![(TriggerEventKind::Enter, a)]);
+
+        let events = trigger.update(volume, vec![a, b].into_iter().collect());
+        assert_eq!(
+            kinds(events),
+            vec![(TriggerEventKind::Stay, a), (TriggerEventKind::Enter, b)]
+        );
+
+        let events = trigger.update(volume, vec![b].into_iter().collect());
+        assert_eq!(
+            kinds(events),
+            vec![(TriggerEventKind::Stay, b), (TriggerEventKind::Exit, a)]
+        );
+
+        let events = trigger.update(volume, BTreeSet::new());
+        assert_eq!(kinds(events), vec![(TriggerEventKind::Exit, b)]);
+        assert!(trigger.update(volume, BTreeSet::new()).is_empty());
+    }
+
+    #[test]
+    fn entities_enter_again_after_leaving() {
+        let entities = entities(2);
+        let (volume, a) = (entities[0], entities[1]);
+        let mut trigger = Trigger::new();
+
+        trigger.update(volume, vec![a].into_iter().collect());
+        trigger.update(volume, BTreeSet::new());
+        let events = trigger.update(volume, vec![a].into_iter().collect());
+        assert_eq!(kinds(events), vec![(TriggerEventKind::Enter, a)]);
+    }
+
+    #[test]
+    fn triggers_only_report_entities_with_their_tag() {
+        let mut world = testing::empty_world(Map::default());
+        let player = world.spawn("player", 700.0, 980.0 - 106.0).unwrap();
+        world.spawn("walker", 760.0, 920.0).unwrap();
+        let coin = world.spawn("coin", 700.0, 910.0).unwrap();
+        run(&mut world, 1);
+
+        let events: Vec<&TriggerEvent> = world.events.read::<TriggerEvent>().collect();
+        assert_eq!(
+            events,
+            vec![&TriggerEvent {
+                kind: TriggerEventKind::Enter,
+                trigger: coin,
+                entity: player,
+            }]
+        );
+    }
+}
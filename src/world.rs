@@ -0,0 +1,157 @@
+use crate::acceleration::Acceleration;
+use crate::bindings::Actions;
+use crate::entity::{Entity, EntityManager};
+use crate::error::Error;
+use crate::event::Events;
+use crate::input::Input;
+use crate::layers::Layers;
+use crate::map::Map;
+use crate::navigation::NavGraphs;
+use crate::prefab::Prefabs;
+use crate::save::SaveGame;
+use crate::surface::Surfaces;
+use std::io::{Read, Write};
+
+/// Everything the per-tick game logic reads and writes, kept apart from what is only needed to
+/// draw it.
+pub struct World<'a> {
+    pub map: Map<'a>,
+    pub entity_manager: EntityManager<'a>,
+    pub surfaces: Surfaces<'a>,
+    pub prefabs: Prefabs,
+    pub events: Events,
+    pub acceleration: Acceleration,
+    pub navigation: NavGraphs,
+}
+
+impl<'a> World<'a> {
+    pub fn new(map: Map<'a>, surfaces: Surfaces<'a>, prefabs: Prefabs) -> Self {
+        Self {
+            map,
+            entity_manager: EntityManager::new(),
+            surfaces,
+            prefabs,
+            events: Events::new(),
+            acceleration: Acceleration::default(),
+            navigation: NavGraphs::new(),
+        }
+    }
+
+    pub fn with_acceleration(mut self, acceleration: Acceleration) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn load() -> Self {
+        let layers = Layers::load().unwrap();
+        World::new(
+            Map::load(&layers),
+            Surfaces::load().unwrap(),
+            Prefabs::load(layers).unwrap(),
+        )
+        .with_acceleration(Acceleration::load().unwrap())
+    }
+
+    /// Adds the entities placed in the map.
+    pub fn spawn_map_entities(&mut self) -> Result<(), Error> {
+        for spawn in self.map.entities.iter() {
+            let mut builder = self.prefabs.resolve(spawn)?.builder(spawn.x, spawn.y);
+            if let Some(name) = &spawn.name {
+                builder = builder.with_name(name.clone());
+            }
+            self.entity_manager.add(builder);
+        }
+        Ok(())
+    }
+
+    /// Hands the actions for the coming tick to every entity's input.
+    pub fn update_inputs(&mut self, actions: &Actions) {
+        for (_, input) in self.entity_manager.iter_mut::<Input>() {
+            input.update(actions);
+        }
+    }
+
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), Error> {
+        SaveGame::new(self.map.clone(), self.entity_manager.save()).write(writer)
+    }
+
+    /// Replaces the map and entities with those from a save. Surfaces and prefabs are left as
+    /// they are, since they come from the assets rather than the save, while the navigation
+    /// graphs are rebuilt for the new map as they're needed.
+    pub fn restore<R: Read>(&mut self, reader: R) -> Result<(), Error> {
+        let mut save = SaveGame::read(reader)?;
+        let layers = self.prefabs.get_layers();
+        save.map.resolve_layers(layers)?;
+        let mut entity_manager = EntityManager::restore(save.entities);
+        entity_manager.resolve_layers(layers)?;
+
+        self.map = save.map;
+        self.entity_manager = entity_manager;
+        self.navigation = NavGraphs::new();
+        Ok(())
+    }
+
+    /// Adds an entity made from the named prefab, with its object placed at the position.
+    pub fn spawn(&mut self, prefab: &str, x: f32, y: f32) -> Result<Entity, Error> {
+        let builder = self.prefabs.builder(prefab, x, y)?;
+        Ok(self.entity_manager.add(builder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Spawn;
+    use crate::object::Movement;
+    use crate::testing;
+    use serde_json::json;
+
+    fn world_with(spawns: Vec<Spawn>) -> World<'static> {
+        let mut map = Map::default();
+        map.entities = spawns;
+        testing::empty_world(map)
+    }
+
+    #[test]
+    fn map_entities_are_spawned_with_their_names_and_overrides() {
+        let mut fast = Spawn::new("walker", 700.0, 920.0);
+        fast.overrides = Some(json!({ "movement": { "max_speed": [20.0, null] } }));
+        let mut world = world_with(vec![
+            Spawn::new("player", 100.0, 200.0).with_name("hero"),
+            fast,
+        ]);
+        world.spawn_map_entities().unwrap();
+
+        let entity_manager = &world.entity_manager;
+        let hero = entity_manager.by_name("hero").unwrap();
+        assert_eq!(
+            entity_manager.get_object(hero).unwrap().rect.point().x,
+            100.0
+        );
+        assert!(entity_manager.has_tag(hero, "player"));
+
+        let walker = entity_manager.tagged("enemy").next().unwrap();
+        let movement = entity_manager.get::<Movement>(walker).unwrap();
+        assert_eq!(movement.get_max_speed(), (Some(20.0), None));
+    }
+
+    #[test]
+    fn unknown_prefabs_in_the_map_are_errors() {
+        let mut world = world_with(vec![Spawn::new("dragon", 0.0, 0.0)]);
+        match world.spawn_map_entities() {
+            Err(Error::UnknownPrefab(name)) => assert_eq!(name, "dragon"),
+            other => panic!("expected an unknown prefab error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bad_overrides_in_the_map_are_errors() {
+        let mut spawn = Spawn::new("walker", 0.0, 0.0);
+        spawn.overrides = Some(json!({ "movment": { "max_speed": [20.0, null] } }));
+        let mut world = world_with(vec![spawn]);
+        match world.spawn_map_entities() {
+            Err(Error::SerdeError(_)) => (),
+            other => panic!("expected a serde error, got {:?}", other),
+        }
+    }
+}